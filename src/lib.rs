@@ -1,20 +1,24 @@
-mod chrom_geo {
+pub mod chrom_geo {
     use std::fmt;
 
+    //a position or length on a chromosome
+    //64 bits are needed for chromosomes of 4 Gb or more
+    pub type Coord = u64;
+
     //this will have to all be manually implemented
     //when we have a "new_chrom" flag
     #[derive(Debug, Ord, Eq, PartialEq, PartialOrd)]
     pub struct ChromPos {
         pub chrom: String,
-        pub index: u32
+        pub index: Coord
     }
 
     //TODO: replace String with str
-    #[derive(Debug, PartialEq, Eq)]
+    #[derive(Debug, Clone, PartialEq, Eq)]
     pub struct ChromSeg {
         pub chrom: String,
-        pub start: u32,
-        pub stop:  u32
+        pub start: Coord,
+        pub stop:  Coord
     }
 
     impl ChromSeg {
@@ -77,10 +81,10 @@ mod chrom_geo {
 
         #[test]
         fn min_max() {
-            let chrom_vec = vec![ChromPos{chrom: "chr4".to_string(), index: 1000},
-                                 ChromPos{chrom: "chr1".to_string(), index: 5000},
-                                 ChromPos{chrom: "chr15".to_string(), index: 100},
-                                 ChromPos{chrom: "chr1".to_string(), index: 3000}];
+            let chrom_vec = [ChromPos{chrom: "chr4".to_string(), index: 1000},
+                             ChromPos{chrom: "chr1".to_string(), index: 5000},
+                             ChromPos{chrom: "chr15".to_string(), index: 100},
+                             ChromPos{chrom: "chr1".to_string(), index: 3000}];
             let min = chrom_vec.iter().min().unwrap();
             let max = chrom_vec.iter().max().unwrap();
             assert_eq!(*min, ChromPos{chrom: "chr1".to_string(), index: 3000});
@@ -116,640 +120,5275 @@ mod chrom_geo {
     }
 }
 
+//writing the output of every subcommand to stdout
+mod output {
+    use std::io::{BufWriter, Write};
+
+    //attempt to write a line to the output
+    //handle the BrokenPipe error elegantly so that these commands can
+    //be used in a pipeline
+    pub(crate) fn write_line<W: Write, T: std::fmt::Display>(output: &mut W, item: &T) {
+        writeln!(output, "{}", item).unwrap_or_else(|err| {
+            match err.kind() {
+                std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+                _ => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        });
+    }
+
+    //run a command writing to a buffered stdout, then flush it, with
+    //BrokenPipe handled as in write_line()
+    pub(crate) fn run_to_stdout<F>(run: F) -> Result<(), String>
+        where F: FnOnce(&mut BufWriter<std::io::StdoutLock>) -> Result<(), String> {
+        let stdout = std::io::stdout();
+        let mut output = BufWriter::new(stdout.lock());
+        run(&mut output)?;
+        output.flush().or_else(|err| match err.kind() {
+            std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+            _ => Err(err.to_string()),
+        })
+    }
+
+    //run a command writing to memory, and split its output into lines
+    #[cfg(test)]
+    pub(crate) fn output_lines<F>(run: F) -> Result<Vec<String>, String>
+        where F: FnOnce(&mut Vec<u8>) -> Result<(), String> {
+        let mut output = Vec::new();
+        run(&mut output)?;
+        Ok(String::from_utf8(output).unwrap().lines().map(|line| line.to_string()).collect())
+    }
+}
+
 pub mod random {
-    use std::io::{Write, BufWriter};
-    use std::collections::{BTreeSet};
+    use std::fmt;
+    use std::io::Write;
     extern crate rand;
-    use rand::Rng;
+    use rand::{Rng, SeedableRng, FromEntropy};
+    use rand::rngs::StdRng;
     use rand::seq::SliceRandom;
-    use super::chrom_geo;
+    use rand::distributions::{Binomial, Distribution, Exp, Normal, Poisson};
+    use super::chrom_geo::{self, Coord};
     use super::chrom_sizes;
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::chrom_sizes::BoundsCheck;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use std::rc::Rc;
      
-    pub fn random_pos(sizes: &Vec<(String, u32)>, rng: &mut rand::prelude::ThreadRng) -> chrom_geo::ChromPos {
+    pub fn random_pos<R: Rng>(sizes: &[(String, Coord)], rng: &mut R) -> chrom_geo::ChromPos {
         let (chrom, size) = sizes.choose(rng).unwrap();
         let index = rng.gen_range(1, size);
         chrom_geo::ChromPos{chrom: chrom.to_string(), index}
     } 
 
-    //TODO: make this a generic Rng
-    pub fn random_seg(sizes: &Vec<(String, u32)>, rng: &mut rand::prelude::ThreadRng) -> chrom_geo::ChromSeg {
+    pub fn random_seg<R: Rng>(sizes: &[(String, Coord)], rng: &mut R) -> chrom_geo::ChromSeg {
             let (chrom, size) = sizes.choose(rng).unwrap();
             let start = rng.gen_range(0, size);
             let stop = rng.gen_range(start, size+1);
             chrom_geo::ChromSeg{chrom: chrom.clone(), start, stop}
     }
 
-    pub fn rand_bed(filename: &str, num_lines: usize) -> Result<(), String> {
-        let mut rng = rand::thread_rng();
-        let chrom_sizes = chrom_sizes::chromsizes_to_map(filename)?;
-        let chrom_size_list: Vec<(String, u32)> = chrom_sizes.into_iter().collect();
-        let mut output = BufWriter::new(std::io::stdout());
-        for _ in 0..num_lines {
-            let line = format!("{}\n", random_seg(&chrom_size_list, &mut rng));
-            //attempt to write
-            //handle the BrokenPipe error elegantly so that this command can
-            //be used in a pipeline
-            output.write(line.as_bytes()).unwrap_or_else(|err| {
-                match err.kind() {
-                    std::io::ErrorKind::BrokenPipe => std::process::exit(0),
-                    _ => {
-                        eprintln!("{}", err);
-                        std::process::exit(1);
-                    }
-                }
-            });
-        }
-        Ok(())
+    //sizes must only contain chromosomes at least 'length' long
+    pub fn random_seg_fixed<R: Rng>(sizes: &[(String, Coord)], length: Coord, rng: &mut R) -> chrom_geo::ChromSeg {
+            let (chrom, size) = sizes.choose(rng).unwrap();
+            let start = rng.gen_range(0, size - length + 1);
+            chrom_geo::ChromSeg{chrom: chrom.clone(), start, stop: start + length}
     }
 
-    //define a Pairwise extension trait for iterators
-    struct Pairwise<T: Iterator>(T);
+    #[derive(Debug)]
+    pub struct RandomConfig {
+        //None indicates that no line count was provided
+        pub num_lines: Option<usize>,
+        //None indicates that segment lengths are random
+        pub length: Option<Coord>,
+        //None indicates BED3 output
+        pub bed6: Option<Bed6Config>,
+        //None indicates that the output is not a bedGraph
+        pub bedgraph: Option<ValueDist>,
+        //probability of a gap between neighbouring sorted segments
+        pub gap_prob: f64,
+        //None indicates that the rng is seeded from the system
+        pub seed: Option<u64>,
+        //what to do with weights outside the genome
+        pub bounds: chrom_sizes::OutOfBounds,
+        //renames the chromosomes of the weights, to match the genome
+        pub renamer: Option<Rc<Renamer>>,
+    }
 
-    //impl Pairwise, which allows Iterators to produce pairs
-    impl<T: Iterator> Iterator for Pairwise<T> {
-        type Item = (T::Item, T::Item);
+    //distribution of the values in a random bedGraph
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ValueDist {
+        Uniform(f64, f64),
+        Normal(f64, f64),
+        Exp(f64),
+        Poisson(f64),
+    }
 
-        fn next(&mut self) -> Option<Self::Item> {
-            Some((self.0.next()?, self.0.next()?))
+    impl ValueDist {
+        //parse a specification of the form NAME:PARAM[,PARAM]
+        //e.g. 'uniform:0,10', 'normal:5,2', 'exp:1', 'poisson:3'
+        pub fn parse(spec: &str) -> Result<ValueDist, String> {
+            let invalid = || format!("Invalid distribution '{}'. Expected one of 'uniform:MIN,MAX', 'normal:MEAN,SD', 'exp:RATE', 'poisson:MEAN'", spec);
+            let mut parts = spec.splitn(2, ':');
+            let name = parts.next().unwrap_or("");
+            let params: Vec<f64> = match parts.next() {
+                None => return Err(invalid()),
+                Some(params) => params.split(',').map(|x| x.trim().parse().map_err(|_| invalid())).collect::<Result<_, _>>()?,
+            };
+            match (name, params.as_slice()) {
+                ("uniform", [min, max]) if min < max => Ok(ValueDist::Uniform(*min, *max)),
+                ("normal", [mean, sd]) if *sd >= 0.0 => Ok(ValueDist::Normal(*mean, *sd)),
+                ("exp", [rate]) if *rate > 0.0 => Ok(ValueDist::Exp(*rate)),
+                ("poisson", [mean]) if *mean > 0.0 => Ok(ValueDist::Poisson(*mean)),
+                _ => Err(invalid()),
+            }
         }
-    }
 
-    //this approach uses a BTreeSet, whereas previous approach used a heap
-    //this approach is marginally (~2%) slower than the heap approach, but it 
-    //guarantees that points are non-duplicate
-    pub fn rand_bed_sorted(filename: &str, num_lines: usize) -> Result<(), String> {
-        let mut rng = rand::thread_rng();
-        let sizes = chrom_sizes::chromsizes_to_map(filename)?;
-        let sizes: Vec<(String, u32)> = sizes.into_iter().collect();
-        let mut chrom_set = BTreeSet::<chrom_geo::ChromPos>::new();
-        while chrom_set.len() < num_lines * 2 {
-            let len = chrom_set.len();
-            chrom_set.insert(random_pos(&sizes, &mut rng));
-            if len == chrom_set.len() {
+        pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+            match *self {
+                ValueDist::Uniform(min, max) => rng.gen_range(min, max),
+                ValueDist::Normal(mean, sd) => Normal::new(mean, sd).sample(rng),
+                ValueDist::Exp(rate) => Exp::new(rate).sample(rng),
+                ValueDist::Poisson(mean) => Poisson::new(mean).sample(rng) as f64,
             }
         }
+    }
 
-        let mut output = BufWriter::new(std::io::stdout());
-        for (p1, p2) in Pairwise(chrom_set.into_iter()) {
-            let seg = match chrom_geo::ChromSeg::from_points(p1, &p2) {
-                //chrom_seg is good
-                Ok(seg) => seg,
-                //chrom_seg not good (likely due to non-matching chromosomes)
-                Err(_) => {
-                    chrom_geo::ChromSeg{chrom: p2.chrom.clone(), start: 0, stop: p2.index}
-                }
-            };
-            let line = format!("{}\n", seg);
-            output.write(line.as_bytes()).unwrap_or_else(|err| {
-                match err.kind() {
-                    std::io::ErrorKind::BrokenPipe => std::process::exit(0),
-                    _ => {
-                        eprintln!("{}", err);
-                        std::process::exit(1);
-                    }
-                }
-            });
+    #[derive(Debug)]
+    pub struct Bed6Config {
+        //names are the prefix followed by the line number
+        pub prefix: String,
+        //None indicates that the score is the segment length (as in bedtools)
+        pub score: Option<u64>,
+        //None indicates a random strand
+        pub strand: Option<char>,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Bed6Line<'a> {
+        pub coords: chrom_geo::ChromSeg,
+        pub prefix: &'a str,
+        pub num: usize,
+        pub score: u64,
+        pub strand: char,
+    }
+
+    impl<'a> Bed6Line<'a> {
+        //num is the 1-based line number
+        pub fn new<R: Rng>(coords: chrom_geo::ChromSeg, num: usize, config: &'a Bed6Config, rng: &mut R) -> Bed6Line<'a> {
+            let score = config.score.unwrap_or(coords.stop - coords.start);
+            let strand = config.strand.unwrap_or_else(|| if rng.gen() { '+' } else { '-' });
+            Bed6Line{coords, prefix: &config.prefix, num, score, strand}
         }
-        Ok(())
     }
-}
 
-pub mod chrom_sizes {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-    use std::collections::{HashMap};
+    impl<'a> fmt::Display for Bed6Line<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}\t{}{}\t{}\t{}", self.coords, self.prefix, self.num, self.score, self.strand)
+        }
+    }
 
-    pub type ChromSizes = HashMap<String, u32>;
+    //write a segment in the output format requested by the config
+    //num is the 1-based line number
+    fn write_seg<W: Write, R: Rng>(output: &mut W, seg: chrom_geo::ChromSeg, num: usize, config: &RandomConfig, rng: &mut R) {
+        match (&config.bedgraph, &config.bed6) {
+            (Some(dist), _) => write_line(output, &BgLine{coords: seg, data: Some(dist.sample(rng).to_string())}),
+            (None, Some(bed6)) => write_line(output, &Bed6Line::new(seg, num, bed6, rng)),
+            (None, None) => write_line(output, &seg),
+        }
+    }
 
-    pub fn chromsizes_to_map(filename: &str) -> Result<HashMap<String, u32>, String> {
-        match File::open(filename) {
-            Err(msg) => Err(format!("Error with '{}': {}", filename, msg)),
-            Ok(handle) => {
-                let handle = BufReader::new(handle);
-                let mut sizes: HashMap<String, u32> = HashMap::new();
-                let mut lineno = 0;
-                for line in handle.lines() {
-                    lineno += 1;
-                    match line {
-                        Err(msg) => return Err(format!("Error with '{}': {}", filename, msg)),
-                        Ok(line) => {
-                            let cols: Vec<&str> = line.split_whitespace().collect();
-                            match cols.len() {
-                                2 => {
-                                    match cols[1].parse() {
-                                        Ok(size) => sizes.insert(cols[0].to_string(), size),
-                                        Err(_) => return Err(format!("Error in '{}', line {}: expected unsigned integer, received '{}'", filename, lineno, cols[1])),
-                                    };
-                                },
-                                _ => {
-                                    return Err(format!("Error in '{}', line {}: expected exactly 2 fields, received {}", filename, lineno, cols.len()))
-                                }
-                            }
-                        }
-                    }   
-                }
-                Ok(sizes)
+    fn new_rng(seed: Option<u64>) -> StdRng {
+        match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    pub fn rand_bed(genome: &chrom_sizes::Genome, config: &RandomConfig) -> Result<(), String> {
+        let mut rng = new_rng(config.seed);
+        let mut chrom_size_list: Vec<(String, Coord)> = genome.iter().map(|(chrom, size)| (chrom.to_string(), size)).collect();
+        if let Some(length) = config.length {
+            if length == 0 {
+                return Err("Segment length must be greater than 0".to_string());
+            }
+            chrom_size_list.retain(|(_, size)| *size >= length);
+            if chrom_size_list.is_empty() {
+                return Err(format!("No chromosome in the genome can hold a segment of length {}", length));
             }
         }
+        run_to_stdout(|output| {
+            for num in 1..=config.num_lines.unwrap_or(usize::MAX) {
+                let seg = match config.length {
+                    Some(length) => random_seg_fixed(&chrom_size_list, length, &mut rng),
+                    None => random_seg(&chrom_size_list, &mut rng),
+                };
+                write_seg(output, seg, num, config, &mut rng);
+            }
+            Ok(())
+        })
     }
 
-    #[cfg(test)]
-    mod test_chrom_sizes {
-        use super::*;
+    //Streams sorted, non-overlapping segments without holding them in memory.
+    //The number of segments on each chromosome is drawn from a binomial
+    //distribution (weighted by the chromosome's share of the remaining genome),
+    //then the points on the chromosome are produced in ascending order by
+    //sampling successive order statistics of uniform draws.
+    //With random lengths, each segment ends at a new point, and starts at
+    //either a new point (after a gap) or the previous segment's stop. The i-th
+    //order statistic is drawn from [0, size - points + 2] and shifted right
+    //by i, so that all points are distinct.
+    //With a fixed length, a new point is only drawn for the start of each run
+    //of bookended segments. It is drawn from [0, size - k*length] and shifted
+    //right by the length of all previous segments, so that segments never overlap.
+    pub struct SortedSegs<'a, R: Rng> {
+        sizes: &'a [(String, Coord)],
+        rng: &'a mut R,
+        length: Option<Coord>,
+        //probability that two neighbouring segments on a chromosome have a gap between them
+        gap_prob: f64,
+        chrom_idx: usize,
+        in_chrom: bool,
+        //segments left to distribute over the chromosomes after chrom_idx
+        segs_left: u64,
+        //the capacity of the chromosomes from each index to the end
+        rest_capacity: Vec<u64>,
+        //number of positions left on the chromosomes after chrom_idx
+        len_left: u64,
+        //segments left to emit on the current chromosome
+        chrom_segs_left: u64,
+        //gaps left to place between the remaining segments on the current chromosome
+        gaps_left: u64,
+        //points left to draw on the current chromosome
+        points_left: u64,
+        //number of slots that the current chromosome's points are drawn from
+        slots: u64,
+        //amount that the next point is shifted right from its slot
+        shift: u64,
+        //last order statistic drawn on the current chromosome
+        last_u: f64,
+        //start of the next segment, if it is bookended to the last one
+        next_start: Option<Coord>,
+    }
 
-        #[test]
-        fn test_hg38_chrom_sizes() {
-            let hg38 = chromsizes_to_map("test/chrom.sizes/hg38.chrom.sizes").unwrap();
-            let pairs: Vec<(&str, u32)> = vec![("chr1", 248956422),
-                                               ("chr2", 242193529),
-                                               ("chrX", 156040895),
-                                               ("chr19", 58617616),
-                                               ("chr19_GL383573v1_alt", 385657),
-                                               ("chrUn_KI270580v1", 1553),
-                                               ("chrUn_KI270394v1", 970)];
-            for (chrom, size) in pairs {
-                assert_eq!(hg38.get(chrom).unwrap(), &size);
+    impl<'a, R: Rng> SortedSegs<'a, R> {
+        //sizes must already be in the desired output order
+        //if num_lines is None, the genome is filled to capacity
+        pub fn new(sizes: &'a [(String, Coord)], num_lines: Option<usize>, length: Option<Coord>, rng: &'a mut R) -> Result<SortedSegs<'a, R>, String> {
+            if length == Some(0) {
+                return Err("Segment length must be greater than 0".to_string());
             }
-            
+            let mut segs = SortedSegs{sizes, rng, length, gap_prob: 1.0, chrom_idx: 0, in_chrom: false,
+                                      segs_left: 0, rest_capacity: Vec::new(), len_left: 0, chrom_segs_left: 0, gaps_left: 0,
+                                      points_left: 0, slots: 0, shift: 0, last_u: 0.0, next_start: None};
+            let mut rest_capacity = vec![0; sizes.len() + 1];
+            for (i, (_, size)) in sizes.iter().enumerate().rev() {
+                rest_capacity[i] = rest_capacity[i + 1] + segs.capacity(*size);
+            }
+            let total = rest_capacity[0];
+            segs.rest_capacity = rest_capacity;
+            segs.segs_left = match (num_lines, length) {
+                (None, _) => total,
+                (Some(n), _) if n as u64 <= total => n as u64,
+                (Some(n), None) => return Err(format!("Cannot place {} sorted, non-intersecting segments in genome (maximum is {})", n, total)),
+                (Some(n), Some(l)) => return Err(format!("Cannot place {} non-overlapping segments of length {} in genome (maximum is {})", n, l, total)),
+            };
+            segs.len_left = sizes.iter().map(|(_, size)| segs.positions(*size)).sum();
+            Ok(segs)
         }
 
-        #[test]
-        fn test_tair10_chrom_sizes() {
-            let tair10 = chromsizes_to_map("test/chrom.sizes/tair10.chrom.sizes").unwrap();
-            let pairs: Vec<(&str, u32)> = vec![("Chr1", 30427671),
-                                               ("Chr2", 19698289),
-                                               ("Chr3", 23459830),
-                                               ("Chr4", 18585056),
-                                               ("Chr5", 26975502),
-                                               ("ChrC", 154478),
-                                               ("ChrM", 366924),];
-            for (chrom, size) in pairs {
-                assert_eq!(tair10.get(chrom).unwrap(), &size);
+        //by default, every pair of neighbouring segments has a gap between them
+        //with a lower probability, some segments are bookended instead
+        pub fn with_gap_prob(mut self, gap_prob: f64) -> Result<SortedSegs<'a, R>, String> {
+            if !(0.0..=1.0).contains(&gap_prob) {
+                return Err(format!("Gap probability must be between 0 and 1, received '{}'", gap_prob));
             }
+            self.gap_prob = gap_prob;
+            Ok(self)
         }
 
-        #[test]
-        fn chrom_sizes_not_exist() {
-            let expect = String::from("Error with 'test/chrom.sizes/does_not_exist': No such file or directory (os error 2)");
-            if let Err(msg) = chromsizes_to_map("test/chrom.sizes/does_not_exist") {
-                assert_eq!(msg, expect)
-            } else {
-                panic!("Expected Err from chromsizes_to_map(), received Ok(_) instead");
+        //the number of non-overlapping segments that can fit on a
+        //chromosome of the given size (assuming every segment is gapped)
+        fn capacity(&self, size: Coord) -> u64 {
+            match self.length {
+                None => size.div_ceil(2),
+                Some(length) => size / length,
             }
         }
 
-        #[test]
-        fn test_badfield1_chrom_sizes() {
-            let expect = String::from("Error in 'test/chrom.sizes/bad_field1.chrom.sizes', line 6: expected exactly 2 fields, received 1");
-            if let Err(msg) = chromsizes_to_map("test/chrom.sizes/bad_field1.chrom.sizes") {
-                assert_eq!(msg, expect)
-            } else {
-                panic!("Expected Err from chromsizes_to_map(), received Ok(_) instead");
+        //the number of positions that a segment's first point can take
+        fn positions(&self, size: Coord) -> u64 {
+            match self.length {
+                None => size + 1,
+                Some(length) => size.saturating_sub(length) + 1,
             }
         }
 
-        #[test]
-        fn test_badfield2_chrom_sizes() {
-            let expect = String::from("Error in 'test/chrom.sizes/bad_field2.chrom.sizes', line 3: expected exactly 2 fields, received 3");
-            if let Err(msg) = chromsizes_to_map("test/chrom.sizes/bad_field2.chrom.sizes") {
-                assert_eq!(msg, expect)
-            } else {
-                panic!("Expected Err from chromsizes_to_map(), received Ok(_) instead");
+        //decide how many segments land on the next chromosome
+        //returns false if there are no chromosomes left
+        fn next_chrom(&mut self) -> bool {
+            if self.in_chrom {
+                self.chrom_idx += 1;
+                self.in_chrom = false;
             }
+            while self.chrom_idx < self.sizes.len() {
+                let size = self.sizes[self.chrom_idx].1;
+                let len = self.positions(size);
+                self.len_left -= len;
+                let mut k = if self.len_left == 0 || self.segs_left == 0 {
+                    self.segs_left
+                } else {
+                    let p = len as f64 / (len + self.len_left) as f64;
+                    Binomial::new(self.segs_left, p).sample(self.rng)
+                };
+                //any segments that don't fit here are carried over
+                k = k.min(self.capacity(size));
+                //make sure the remaining chromosomes can hold the rest
+                k = k.max(self.segs_left.saturating_sub(self.rest_capacity[self.chrom_idx + 1]));
+                self.segs_left -= k;
+                if k > 0 {
+                    let gaps = Binomial::new(k - 1, self.gap_prob).sample(self.rng);
+                    let (points, slots) = match self.length {
+                        None => (k + 1 + gaps, size + 2 - (k + 1 + gaps)),
+                        Some(length) => (gaps + 1, size - k * length + 1),
+                    };
+                    self.chrom_segs_left = k;
+                    self.gaps_left = gaps;
+                    self.points_left = points;
+                    self.slots = slots;
+                    self.shift = 0;
+                    self.last_u = 0.0;
+                    self.next_start = None;
+                    self.in_chrom = true;
+                    return true;
+                }
+                self.chrom_idx += 1;
+            }
+            false
         }
 
-        #[test]
-        fn test_badsize1_chrom_sizes() {
-            let expect = String::from("Error in 'test/chrom.sizes/bad_size1.chrom.sizes', line 2: expected unsigned integer, received '-19698289'");
-            if let Err(msg) = chromsizes_to_map("test/chrom.sizes/bad_size1.chrom.sizes") {
-                assert_eq!(msg, expect)
-            } else {
-                panic!("Expected Err from chromsizes_to_map(), received Ok(_) instead");
+        //draw the next point on the current chromosome
+        fn next_point(&mut self) -> Coord {
+            let v: f64 = 1.0 - self.rng.gen::<f64>();
+            self.last_u = 1.0 - (1.0 - self.last_u) * v.powf(1.0 / self.points_left as f64);
+            let slot = ((self.last_u * self.slots as f64) as u64).min(self.slots - 1);
+            let index = slot + self.shift;
+            self.points_left -= 1;
+            if self.length.is_none() {
+                self.shift += 1;
             }
+            index
         }
 
-        #[test]
-        fn test_badsize2_chrom_sizes() {
-            let expect = String::from("Error in 'test/chrom.sizes/bad_size2.chrom.sizes', line 4: expected unsigned integer, received 'apple'");
-            if let Err(msg) = chromsizes_to_map("test/chrom.sizes/bad_size2.chrom.sizes") {
-                assert_eq!(msg, expect)
-            } else {
-                panic!("Expected Err from chromsizes_to_map(), received Ok(_) instead");
+        //decide whether there is a gap before the next segment, such that the
+        //gaps are spread evenly over the remaining boundaries
+        fn next_is_gap(&mut self) -> bool {
+            let boundaries = self.chrom_segs_left;
+            let gap = (self.rng.gen::<f64>() * boundaries as f64) < self.gaps_left as f64;
+            if gap {
+                self.gaps_left -= 1;
             }
+            gap
         }
     }
-}
 
-pub mod bedgraph {
-    use super::chrom_geo;
-    use std::fmt;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
+    impl<'a, R: Rng> Iterator for SortedSegs<'a, R> {
+        type Item = chrom_geo::ChromSeg;
 
-    #[derive(Debug, PartialEq, Eq)]
-    pub struct BgLine {
-        pub coords: chrom_geo::ChromSeg,
-        //TODO: turn the data into a str that references another field "raw line"
-        pub data: Option<String>,
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.chrom_segs_left == 0 && !self.next_chrom() {
+                return None;
+            }
+            let start = match self.next_start.take() {
+                Some(start) => start,
+                None => self.next_point(),
+            };
+            let stop = match self.length {
+                None => self.next_point(),
+                Some(length) => {
+                    self.shift += length;
+                    start + length
+                },
+            };
+            self.chrom_segs_left -= 1;
+            if self.chrom_segs_left > 0 && !self.next_is_gap() {
+                self.next_start = Some(stop);
+            }
+            Some(chrom_geo::ChromSeg{chrom: self.sizes[self.chrom_idx].0.clone(), start, stop})
+        }
     }
 
-    impl BgLine {
-        fn new(input_str: &str) -> Result<BgLine, String> {
-            //TODO: avoid iterating over the entire line by using enumerate
-            let cols: Vec<&str> = input_str.split_whitespace().collect();
-            if cols.len() < 3 {
-                Err(format!("Invalid number of columns [{}] in line:\n{}", cols.len(), input_str))
-            } else {
-                //use lifetimes to make this work, instead of copying the string
-                let chrom = cols[0].to_string();
-                let start: u32 = cols[1].parse().unwrap();
-                let stop:  u32 = cols[2].parse().unwrap();
-                if cols.len() > 3 {
-                    Ok( BgLine{coords: chrom_geo::ChromSeg{chrom, start, stop}, data: Some(cols[3..].join("\t")) } )
-                } else {
-                    Ok( BgLine{coords: chrom_geo::ChromSeg{chrom, start, stop}, data: None} )
+    //used for '--sorted', '--no-overlap' and '--bedgraph', as sorted output is
+    //always non-overlapping
+    pub fn rand_bed_sorted(genome: &chrom_sizes::Genome, config: &RandomConfig) -> Result<(), String> {
+        let mut rng = new_rng(config.seed);
+        //the chromosomes are in the order of the genome file (see 'ironbed sort -g')
+        let sizes: Vec<(String, Coord)> = genome.iter().map(|(chrom, size)| (chrom.to_string(), size)).collect();
+        //the segment iterator holds onto the rng, so strands and values come from a second one
+        let mut value_rng = StdRng::from_rng(&mut rng).map_err(|err| err.to_string())?;
+        let segs = SortedSegs::new(&sizes, config.num_lines, config.length, &mut rng)?.with_gap_prob(config.gap_prob)?;
+        run_to_stdout(|output| {
+            for (num, seg) in segs.enumerate() {
+                write_seg(output, seg, num + 1, config, &mut value_rng);
+            }
+            Ok(())
+        })
+    }
+
+    //Samples positions with probability proportional to the values of a
+    //bedGraph track. Regions with a value of zero, and regions missing
+    //from the bedGraph, are never sampled.
+    #[derive(Debug)]
+    pub struct WeightedSegs {
+        segs: Vec<chrom_geo::ChromSeg>,
+        //running total of value * length, up to and including each segment
+        cumulative: Vec<f64>,
+    }
+
+    impl WeightedSegs {
+        //if bounds is given, lines outside the genome are rejected, clipped or dropped
+        //(after renaming their chromosomes, if renamer is given)
+        pub fn from_bedgraph(filename: &str, bounds: Option<BoundsCheck>, renamer: Option<&Rc<Renamer>>) -> Result<WeightedSegs, String> {
+            let mut reader = BgIterator::new(filename).map_err(|err| format!("{}: '{}'", err, filename))?;
+            if let Some(renamer) = renamer {
+                reader = reader.with_renamer(renamer.clone());
+            }
+            if let Some(bounds) = bounds {
+                reader = reader.with_bounds(bounds);
+            }
+            let mut segs = Vec::new();
+            let mut cumulative = Vec::new();
+            let mut total = 0.0;
+            let mut lineno = 0;
+            while let Some(line) = reader.try_next()? {
+                lineno += 1;
+                let value = line.data.as_ref().and_then(|data| data.split('\t').next()).unwrap_or("");
+                let value: f64 = match value.parse() {
+                    Ok(value) if value >= 0.0 => value,
+                    _ => return Err(format!("Error in '{}', line {}: expected non-negative number, received '{}'", filename, lineno, value)),
+                };
+                if value == 0.0 || line.coords.start >= line.coords.stop {
+                    continue;
                 }
+                total += value * (line.coords.stop - line.coords.start) as f64;
+                segs.push(line.coords);
+                cumulative.push(total);
             }
+            if segs.is_empty() {
+                return Err(format!("Error in '{}': no regions with a positive value", filename));
+            }
+            Ok( WeightedSegs{segs, cumulative} )
         }
 
-        //TODO: implement this with the new_chrom to minimize string comparison?
-        pub fn starts_after(&self, pos: &chrom_geo::ChromPos) -> bool {
-            self.coords.start_pos() > *pos 
+        pub fn total(&self) -> f64 {
+            *self.cumulative.last().unwrap()
         }
 
-        pub fn ends_before(&self, pos: &chrom_geo::ChromPos) -> bool {
-            self.coords.stop_pos() <= *pos 
+        //find the position at x, where 0 <= x < total()
+        pub fn locate(&self, x: f64) -> chrom_geo::ChromPos {
+            let idx = self.cumulative.partition_point(|c| *c <= x).min(self.segs.len() - 1);
+            let prev = if idx > 0 { self.cumulative[idx - 1] } else { 0.0 };
+            let seg = &self.segs[idx];
+            let len = seg.stop - seg.start;
+            let frac = (x - prev) / (self.cumulative[idx] - prev);
+            let offset = ((frac * len as f64) as Coord).min(len - 1);
+            chrom_geo::ChromPos{chrom: seg.chrom.clone(), index: seg.start + offset}
         }
-    }
 
-    impl fmt::Display for BgLine {
-        
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            match self.data {
-                Some(ref data) =>  
-                    write!(f, "{}\t{}\t{}\t{}", self.coords.chrom, self.coords.start, self.coords.stop, data),
-                None =>
-                    write!(f, "{}\t{}\t{}", self.coords.chrom, self.coords.start, self.coords.stop)
-            }
-            
+        pub fn sample<R: Rng>(&self, rng: &mut R) -> chrom_geo::ChromPos {
+            self.locate(rng.gen::<f64>() * self.total())
+        }
+
+        //sample num_points positions, streamed in bedGraph order
+        pub fn sorted<'a, R: Rng>(&'a self, num_points: usize, rng: &'a mut R) -> WeightedPoints<'a, R> {
+            WeightedPoints{weights: self, rng, points_left: num_points as u64, last_u: 0.0}
         }
     }
 
-    #[derive(Debug)]
-    pub struct BgIterator {
-        reader: BufReader<File>,
-        lineno: u32
+    //sorted positions, drawn as successive order statistics (see SortedSegs)
+    pub struct WeightedPoints<'a, R: Rng> {
+        weights: &'a WeightedSegs,
+        rng: &'a mut R,
+        points_left: u64,
+        last_u: f64,
     }
 
-    impl BgIterator {
-        pub fn new(fname: &str) -> Result<BgIterator, String> {
-            //consider removing... this information is in the reader
-            match File::open(fname) {
-                Err(x) => Err(x.to_string()),
-                Ok(handle) =>
-                    Ok( BgIterator{ reader: BufReader::new(handle), lineno: 0 }   )
+    impl<'a, R: Rng> Iterator for WeightedPoints<'a, R> {
+        type Item = chrom_geo::ChromPos;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.points_left == 0 {
+                return None;
             }
+            let v: f64 = 1.0 - self.rng.gen::<f64>();
+            self.last_u = 1.0 - (1.0 - self.last_u) * v.powf(1.0 / self.points_left as f64);
+            self.points_left -= 1;
+            Some(self.weights.locate(self.last_u * self.weights.total()))
         }
     }
 
-    impl Iterator for BgIterator {
-        type Item = BgLine;
+    //turn a sampled position into a segment, either 1 base long or
+    //config.length bases long (clipped to the end of the chromosome)
+    //the chromosome is named as it is in the genome
+    fn seg_at(pos: chrom_geo::ChromPos, genome: &chrom_sizes::Genome, config: &RandomConfig) -> Result<chrom_geo::ChromSeg, String> {
+        let id = match genome.id(&pos.chrom) {
+            Some(id) => id,
+            None => return Err(format!("Chromosome '{}' is not in the genome file", pos.chrom)),
+        };
+        let stop = pos.index.saturating_add(config.length.unwrap_or(1)).min(genome.size(id));
+        Ok( chrom_geo::ChromSeg{chrom: genome.name(id).to_string(), start: pos.index, stop} )
+    }
 
-        fn next(&mut self) -> Option<Self::Item> {
-            //TODO: allocate to be the size of the previous line?
-            let mut temp = String::new();
-            match self.reader.read_line(&mut temp) {
-                Err(err) => {eprintln!("{}", err.to_string()); None},
-                Ok(bytes) => {
-                    match bytes {
-                        0 => None,
-                        _ => {
-                            self.lineno += 1;
-                            Some(BgLine::new(&temp).unwrap())
-                        }
-                    }
+    //sample segments in proportion to the values in weights_file
+    //if sorted is true, output follows the order of weights_file
+    pub fn rand_bed_weighted(genome: &chrom_sizes::Genome, weights_file: &str, sorted: bool, config: &RandomConfig) -> Result<(), String> {
+        let mut rng = new_rng(config.seed);
+        let bounds = BoundsCheck::new(Rc::new(genome.clone()), config.bounds);
+        let weights = WeightedSegs::from_bedgraph(weights_file, Some(bounds), config.renamer.as_ref())?;
+        if config.length == Some(0) {
+            return Err("Segment length must be greater than 0".to_string());
+        }
+        if !sorted {
+            return run_to_stdout(|output| {
+                for num in 1..=config.num_lines.unwrap_or(usize::MAX) {
+                    let pos = weights.sample(&mut rng);
+                    write_seg(output, seg_at(pos, genome, config)?, num, config, &mut rng);
                 }
-            }
+                Ok(())
+            });
         }
+        let num_points = match config.num_lines {
+            Some(n) => n,
+            None => return Err("Sorted weighted sampling requires a line count".to_string()),
+        };
+        let mut value_rng = StdRng::from_rng(&mut rng).map_err(|err| err.to_string())?;
+        run_to_stdout(|output| {
+            for (num, pos) in weights.sorted(num_points, &mut rng).enumerate() {
+                write_seg(output, seg_at(pos, genome, config)?, num + 1, config, &mut value_rng);
+            }
+            Ok(())
+        })
     }
+
     #[cfg(test)]
-    mod test_bedgraph {
+    mod test_random {
         use super::*;
-        
-        //helper functions for checking chrom_seg and data
-        fn check_segment(line: &Option<BgLine>, coords: chrom_geo::ChromSeg) {
-            if let Some(line) = line {
-                assert_eq!(line.coords, coords);
-            } else {
-                panic!(format!("Last line is None: {:?}", line));
+
+        fn check_sorted(segs: &[chrom_geo::ChromSeg]) {
+            for seg in segs {
+                assert!(seg.start < seg.stop, "empty segment: {}", seg);
+            }
+            for pair in segs.windows(2) {
+                assert!(pair[0].stop_pos() < pair[1].start_pos(), "{} and {} out of order", pair[0], pair[1]);
             }
         }
 
-        fn check_data(line: &Option<BgLine>, data: Option<String>) {
-            if let Some(line) = line {
-                assert_eq!(line.data, data);
-            } else {
-                panic!(format!("Last line is None: {:?}", line));
+        #[test]
+        fn sorted_segs_count() {
+            let sizes = vec![("chr1".to_string(), 5000), ("chr2".to_string(), 3000), ("chrM".to_string(), 16)];
+            let mut rng = StdRng::seed_from_u64(26);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(500), None, &mut rng).unwrap().collect();
+            assert_eq!(segs.len(), 500);
+            check_sorted(&segs);
+            for seg in &segs {
+                let size = sizes.iter().find(|(chrom, _)| *chrom == seg.chrom).unwrap().1;
+                assert!(seg.stop <= size);
             }
         }
-        
+
         #[test]
-        fn starts_after() {
-            let bg = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
-            let pos = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1980};
-            let test_values = bg.map(|x| x.starts_after(&pos)).collect::<Vec<bool>>();;
-            let expected_values = vec![false, false, false, false, false, true, true, true, true];
-            assert_eq!(test_values, expected_values);
-            let bg = BgIterator::new("test/unionbedg/long.bg").unwrap();
-            let pos = chrom_geo::ChromPos{chrom: "chr2".to_string(), index: 4000};
-            let test_values = bg.map(|x| x.starts_after(&pos)).collect::<Vec<bool>>();
-            let expected_values = vec![false, false, false, false, true, true, true, true];
-            assert_eq!(test_values, expected_values);
+        fn sorted_segs_many_chroms() {
+            //a fragmented assembly, whose capacity is only summed once
+            let sizes: Vec<(String, Coord)> = (0..200_000).map(|i| (format!("scaffold{}", i), 100)).collect();
+            let mut rng = StdRng::seed_from_u64(26);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(10), None, &mut rng).unwrap().collect();
+            assert_eq!(segs.len(), 10);
         }
 
         #[test]
-        fn ends_before() {
-            let bg = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
-            let pos = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1980};
-            let test_values = bg.map(|x| x.ends_before(&pos)).collect::<Vec<bool>>();;
-            let expected_values = vec![true, true, true, true, false, false, false, false, false];
-            assert_eq!(test_values, expected_values);
-            let bg = BgIterator::new("test/unionbedg/long.bg").unwrap();
-            let pos = chrom_geo::ChromPos{chrom: "chr2".to_string(), index: 4000};
-            let test_values = bg.map(|x| x.ends_before(&pos)).collect::<Vec<bool>>();
-            let expected_values = vec![true, true, true, true, false, false, false, false];
-            assert_eq!(test_values, expected_values);
+        fn sorted_segs_fill() {
+            //without a line count, the genome is filled to capacity
+            let sizes = vec![("chr1".to_string(), 11), ("chr2".to_string(), 8)];
+            let mut rng = StdRng::seed_from_u64(26);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, None, None, &mut rng).unwrap().collect();
+            assert_eq!(segs.len(), 6 + 4);
+            check_sorted(&segs);
         }
 
         #[test]
-        fn line_count() {
-            //create a bedgraph iterator for a test file with 9 lines
-            let bedgraph = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
-            //check the number of lines
-            let count = bedgraph.count();
-            assert_eq!(count, 9);
+        fn sorted_segs_too_many() {
+            let sizes = vec![("chr1".to_string(), 11)];
+            let mut rng = StdRng::seed_from_u64(26);
+            match SortedSegs::new(&sizes, Some(7), None, &mut rng) {
+                Err(msg) => assert_eq!(msg, "Cannot place 7 sorted, non-intersecting segments in genome (maximum is 6)"),
+                Ok(_) => panic!("Expected Err from SortedSegs::new(), received Ok(_) instead"),
+            }
         }
 
         #[test]
-        fn iterator_next() {
-            let mut bedgraph = BgIterator::new("test/unionbedg/1.bg").unwrap();
-            let last_line = bedgraph.next();
-            check_segment(&last_line, chrom_geo::ChromSeg{chrom: "chr1".to_string(), start: 1000, stop: 1500 } );
-            check_data(&last_line, Some("10".to_string()));;
-            let last_line = bedgraph.next();
-            check_segment(&last_line, chrom_geo::ChromSeg{chrom: "chr1".to_string(), start: 2000, stop: 2100 } );
-            check_data(&last_line, Some("20".to_string()));;
-            let last_line = bedgraph.next();
-            assert_eq!(last_line, None);
+        fn sorted_segs_uniform() {
+            //both chromosomes have the same size, so they should get similar counts
+            let sizes = vec![("chr1".to_string(), 1_000_000), ("chr2".to_string(), 1_000_000)];
+            let mut rng = StdRng::seed_from_u64(26);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(10000), None, &mut rng).unwrap().collect();
+            check_sorted(&segs);
+            let chr1 = segs.iter().filter(|seg| seg.chrom == "chr1").count();
+            assert!(chr1 > 4700 && chr1 < 5300, "chr1 received {} of 10000 segments", chr1);
+            //the first half of chr1 should hold about half of its segments
+            let low = segs.iter().filter(|seg| seg.chrom == "chr1" && seg.start < 500_000).count();
+            assert!((low as f64 / chr1 as f64 - 0.5).abs() < 0.05);
         }
-        
+
+        #[test]
+        fn no_overlap_fixed_length() {
+            let sizes = vec![("chr1".to_string(), 1000), ("chr2".to_string(), 999), ("chrM".to_string(), 99)];
+            let mut rng = StdRng::seed_from_u64(27);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(15), Some(100), &mut rng).unwrap().collect();
+            assert_eq!(segs.len(), 15);
+            for seg in &segs {
+                assert_eq!(seg.stop - seg.start, 100);
+                assert!(seg.chrom != "chrM");
+            }
+            for pair in segs.windows(2) {
+                assert!(pair[0].stop_pos() <= pair[1].start_pos(), "{} and {} overlap", pair[0], pair[1]);
+            }
+            //chr1 holds 10 segments and chr2 holds 9, so 19 is the maximum
+            let mut rng = StdRng::seed_from_u64(27);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(19), Some(100), &mut rng).unwrap().collect();
+            assert_eq!(segs.len(), 19);
+            assert_eq!(segs[9], chrom_geo::ChromSeg{chrom: "chr1".to_string(), start: 900, stop: 1000});
+        }
+
+        #[test]
+        fn sorted_segs_bookended() {
+            let sizes = vec![("chr1".to_string(), 100_000), ("chr2".to_string(), 50_000)];
+            let mut rng = StdRng::seed_from_u64(29);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(1000), None, &mut rng).unwrap()
+                                                           .with_gap_prob(0.0).unwrap().collect();
+            assert_eq!(segs.len(), 1000);
+            for pair in segs.windows(2) {
+                assert!(pair[0].start < pair[0].stop);
+                if pair[0].chrom == pair[1].chrom {
+                    assert_eq!(pair[0].stop, pair[1].start);
+                }
+            }
+            //with fixed lengths, each chromosome holds a single run
+            let mut rng = StdRng::seed_from_u64(29);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(1000), Some(50), &mut rng).unwrap()
+                                                           .with_gap_prob(0.0).unwrap().collect();
+            assert_eq!(segs.len(), 1000);
+            for pair in segs.windows(2) {
+                if pair[0].chrom == pair[1].chrom {
+                    assert_eq!(pair[0].stop, pair[1].start);
+                }
+            }
+        }
+
+        #[test]
+        fn sorted_segs_mixed_gaps() {
+            let sizes = vec![("chr1".to_string(), 1_000_000)];
+            let mut rng = StdRng::seed_from_u64(29);
+            let segs: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(1001), None, &mut rng).unwrap()
+                                                           .with_gap_prob(0.5).unwrap().collect();
+            assert_eq!(segs.len(), 1001);
+            let bookended = segs.windows(2).filter(|pair| pair[0].stop == pair[1].start).count();
+            assert!(bookended > 400 && bookended < 600, "{} of 1000 boundaries bookended", bookended);
+            for pair in segs.windows(2) {
+                assert!(pair[0].stop <= pair[1].start);
+            }
+        }
+
+        #[test]
+        fn sorted_segs_seeded() {
+            let sizes = vec![("chr1".to_string(), 5000), ("chr2".to_string(), 3000)];
+            let mut rng1 = StdRng::seed_from_u64(29);
+            let mut rng2 = StdRng::seed_from_u64(29);
+            let segs1: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(50), None, &mut rng1).unwrap().collect();
+            let segs2: Vec<chrom_geo::ChromSeg> = SortedSegs::new(&sizes, Some(50), None, &mut rng2).unwrap().collect();
+            assert_eq!(segs1, segs2);
+        }
+
+        #[test]
+        fn value_dist_parse() {
+            assert_eq!(ValueDist::parse("uniform:0,10"), Ok(ValueDist::Uniform(0.0, 10.0)));
+            assert_eq!(ValueDist::parse("normal:5, 2.5"), Ok(ValueDist::Normal(5.0, 2.5)));
+            assert_eq!(ValueDist::parse("exp:1"), Ok(ValueDist::Exp(1.0)));
+            assert_eq!(ValueDist::parse("poisson:3"), Ok(ValueDist::Poisson(3.0)));
+            let expect = "Invalid distribution 'uniform:10,0'. Expected one of 'uniform:MIN,MAX', 'normal:MEAN,SD', 'exp:RATE', 'poisson:MEAN'";
+            assert_eq!(ValueDist::parse("uniform:10,0"), Err(expect.to_string()));
+            for spec in &["uniform", "exp:apple", "poisson:1,2", "beta:1,2"] {
+                assert!(ValueDist::parse(spec).is_err(), "'{}' should not parse", spec);
+            }
+            let mut rng = StdRng::seed_from_u64(29);
+            let dist = ValueDist::parse("uniform:2,3").unwrap();
+            assert!((0..100).map(|_| dist.sample(&mut rng)).all(|x| (2.0..3.0).contains(&x)));
+        }
+
+        #[test]
+        fn weighted_sampling() {
+            let weights = WeightedSegs::from_bedgraph("test/random/weights.bg", None, None).unwrap();
+            //chr1:200-300 has weight 100, chr2:0-100 has weight 300
+            assert_eq!(weights.total(), 400.0);
+            let mut rng = StdRng::seed_from_u64(30);
+            let points: Vec<chrom_geo::ChromPos> = (0..4000).map(|_| weights.sample(&mut rng)).collect();
+            for pos in &points {
+                match pos.chrom.as_str() {
+                    "chr1" => assert!(pos.index >= 200 && pos.index < 300, "sampled zero region {:?}", pos),
+                    _ => assert!(pos.index < 100, "sampled zero region {:?}", pos),
+                }
+            }
+            let chr2 = points.iter().filter(|pos| pos.chrom == "chr2").count();
+            assert!(chr2 > 2850 && chr2 < 3150, "chr2 received {} of 4000 points", chr2);
+        }
+
+        #[test]
+        fn weighted_sorted() {
+            let weights = WeightedSegs::from_bedgraph("test/random/weights.bg", None, None).unwrap();
+            let mut rng = StdRng::seed_from_u64(30);
+            let points: Vec<chrom_geo::ChromPos> = weights.sorted(1000, &mut rng).collect();
+            assert_eq!(points.len(), 1000);
+            for pair in points.windows(2) {
+                assert!(pair[0] <= pair[1]);
+            }
+            assert!(points.iter().all(|pos| (pos.chrom == "chr1" && pos.index >= 200 && pos.index < 300)
+                                            || (pos.chrom == "chr2" && pos.index < 100)));
+        }
+
+        #[test]
+        fn weighted_all_zero() {
+            let expect = "Error in 'test/unionbedg/empty-1.bg': no regions with a positive value";
+            match WeightedSegs::from_bedgraph("test/unionbedg/empty-1.bg", None, None) {
+                Err(msg) => assert_eq!(msg, expect),
+                Ok(_) => panic!("Expected Err from WeightedSegs::from_bedgraph(), received Ok(_) instead"),
+            }
+        }
+
+        #[test]
+        fn bed6_format() {
+            let mut rng = StdRng::seed_from_u64(28);
+            let config = Bed6Config{prefix: "rand_".to_string(), score: None, strand: Some('-')};
+            let seg = chrom_geo::ChromSeg{chrom: "chr2".to_string(), start: 100, stop: 250};
+            let line = Bed6Line::new(seg, 7, &config, &mut rng);
+            assert_eq!(line.to_string(), "chr2\t100\t250\trand_7\t150\t-");
+            let config = Bed6Config{prefix: String::new(), score: Some(0), strand: None};
+            let strands: Vec<char> = (1..=100).map(|num| {
+                let seg = chrom_geo::ChromSeg{chrom: "chr2".to_string(), start: 100, stop: 250};
+                let line = Bed6Line::new(seg, num, &config, &mut rng);
+                assert_eq!(line.to_string(), format!("chr2\t100\t250\t{}\t0\t{}", num, line.strand));
+                line.strand
+            }).collect();
+            assert!(strands.contains(&'+') && strands.contains(&'-'));
+        }
+
+        #[test]
+        fn no_overlap_too_many() {
+            let sizes = vec![("chr1".to_string(), 1000), ("chr2".to_string(), 999)];
+            let mut rng = StdRng::seed_from_u64(27);
+            match SortedSegs::new(&sizes, Some(20), Some(100), &mut rng) {
+                Err(msg) => assert_eq!(msg, "Cannot place 20 non-overlapping segments of length 100 in genome (maximum is 19)"),
+                Ok(_) => panic!("Expected Err from SortedSegs::new(), received Ok(_) instead"),
+            }
+        }
+    }
+}
+
+pub mod chrom_sizes {
+    use std::cmp::Ordering;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+    use std::collections::{HashMap};
+    use flate2::read::MultiGzDecoder;
+    use std::rc::Rc;
+    use super::chrom_alias::{AliasTable, Renamer};
+    use super::chrom_geo::{ChromSeg, Coord};
+
+    //open a file, decompressing it if it starts with the gzip magic bytes
+    //multi-member files (such as BGZF) are read to the end
+    pub fn open_maybe_gz(filename: &str) -> Result<Box<dyn BufRead>, String> {
+        let handle = File::open(filename).map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+        let mut handle = BufReader::new(handle);
+        if is_gz(filename, &mut handle)? {
+            Ok(Box::new(BufReader::new(MultiGzDecoder::new(handle))))
+        } else {
+            Ok(Box::new(handle))
+        }
+    }
+
+    fn is_gz<R: BufRead>(filename: &str, handle: &mut R) -> Result<bool, String> {
+        match handle.fill_buf() {
+            Err(msg) => Err(format!("Error with '{}': {}", filename, msg)),
+            Ok(buf) => Ok(buf.starts_with(&[0x1f, 0x8b])),
+        }
+    }
+
+    //chrom.sizes of the built-in genomes, which can be used in place of a genome file
+    //hg38, mm10 and tair10 include all sequences (e.g. alts and unplaced contigs),
+    //while the others only include the primary chromosomes
+    pub const PRESETS: &[(&str, &str)] = &[
+        ("hg19", include_str!("../genomes/hg19.chrom.sizes")),
+        ("hg38", include_str!("../genomes/hg38.chrom.sizes")),
+        ("hg38-short", include_str!("../genomes/hg38-short.chrom.sizes")),
+        ("mm10", include_str!("../genomes/mm10.chrom.sizes")),
+        ("mm39", include_str!("../genomes/mm39.chrom.sizes")),
+        ("dm6", include_str!("../genomes/dm6.chrom.sizes")),
+        ("sacCer3", include_str!("../genomes/sacCer3.chrom.sizes")),
+        ("tair10", include_str!("../genomes/tair10.chrom.sizes")),
+    ];
+
+    //the kinds of file that a genome can be read from, other than .fai
+    enum GenomeFormat {
+        Sizes,
+        Fasta,
+        Sam,
+        Bam,
+        Vcf,
+    }
+
+    impl GenomeFormat {
+        //guess the format from the first (decompressed) bytes of a file
+        fn detect(buf: &[u8]) -> GenomeFormat {
+            if buf.starts_with(b"BAM\x01") {
+                GenomeFormat::Bam
+            } else if buf.starts_with(b">") {
+                GenomeFormat::Fasta
+            } else if buf.starts_with(b"##fileformat=VCF") {
+                GenomeFormat::Vcf
+            } else if buf.starts_with(b"@") {
+                GenomeFormat::Sam
+            } else {
+                GenomeFormat::Sizes
+            }
+        }
+    }
+
+    //split the inside of a VCF structured header line (e.g. 'ID=chr1,length=100')
+    //into key/value pairs, ignoring commas inside quoted values
+    fn vcf_fields(line: &str) -> Vec<(&str, &str)> {
+        let mut fields = Vec::new();
+        let mut in_quotes = false;
+        let mut start = 0;
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ',' if !in_quotes => {
+                    fields.push(&line[start..i]);
+                    start = i + 1;
+                },
+                _ => (),
+            }
+        }
+        fields.push(&line[start..]);
+        fields.into_iter().map(|field| {
+            let mut parts = field.splitn(2, '=');
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        }).collect()
+    }
+
+    //the longest reference name accepted in a BAM header, so a corrupt
+    //header can't make us allocate gigabytes
+    const MAX_BAM_NAME: usize = 1 << 20;
+
+    //one line of a FASTA index
+    #[derive(Debug)]
+    struct FaiRecord {
+        name: String,
+        length: Coord,
+        //offset of the first base in the (uncompressed) file
+        offset: u64,
+        line_bases: u64,
+        line_width: u64,
+        //set once a short line (the last line of the sequence) is seen
+        ended: bool,
+        //the line number of a blank line in the sequence, which is only
+        //allowed at its end
+        blank: Option<usize>,
+    }
+
+    fn write_fai(filename: &str, index: &[FaiRecord]) -> Result<(), String> {
+        let handle = File::create(filename).map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+        let mut output = BufWriter::new(handle);
+        for rec in index {
+            writeln!(output, "{}\t{}\t{}\t{}\t{}", rec.name, rec.length, rec.offset, rec.line_bases, rec.line_width)
+                .map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+        }
+        output.flush().map_err(|msg| format!("Error with '{}': {}", filename, msg))
+    }
+
+    //A list of chromosomes and their sizes, in the order they were added.
+    //Each chromosome is also given an ID, which is its index in that order.
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    pub struct Genome {
+        names: Vec<String>,
+        sizes: Vec<Coord>,
+        ids: HashMap<String, usize>,
+        //other names for the chromosomes, from an alias table
+        aliases: HashMap<String, usize>,
+    }
+
+    impl Genome {
+        pub fn new() -> Genome {
+            Genome::default()
+        }
+
+        //read a chrom.sizes file
+        pub fn from_file(filename: &str) -> Result<Genome, String> {
+            Genome::from_file_mode(filename, SizesMode::Strict)
+        }
+
+        //read a chrom.sizes file, parsing it according to 'mode'
+        pub fn from_file_mode(filename: &str, mode: SizesMode) -> Result<Genome, String> {
+            match File::open(filename) {
+                Err(msg) => Err(format!("Error with '{}': {}", filename, msg)),
+                Ok(handle) => Genome::from_sizes(filename, BufReader::new(handle), mode),
+            }
+        }
+
+        //read a genome from a chrom.sizes file, a FASTA index (.fai), a FASTA
+        //file, the @SQ lines of a SAM/BAM header, or the ##contig lines of a
+        //VCF header (any of which, except .fai, may be gzip or BGZF compressed)
+        //if write_fai is true, the genome must be an uncompressed FASTA file
+        //(a BGZF file would also need a .gzi index), and its index is
+        //written to '<filename>.fai' while scanning
+        //if there is no file called 'filename', but there is a built-in
+        //genome with that name (see PRESETS), the built-in genome is used
+        //chrom.sizes files are parsed according to 'mode'
+        pub fn load(filename: &str, write_fai: bool, mode: SizesMode) -> Result<Genome, String> {
+            if !std::path::Path::new(filename).exists() {
+                if let Some(genome) = Genome::preset(filename) {
+                    return genome;
+                }
+            }
+            if filename.ends_with(".fai") && !write_fai {
+                return Genome::from_fai(filename);
+            }
+            if write_fai {
+                let handle = File::open(filename).map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                if is_gz(filename, &mut BufReader::new(handle))? {
+                    return Err(format!("Error with '{}': cannot write a FASTA index for a compressed file (use 'samtools faidx' to index BGZF files)", filename));
+                }
+            }
+            let mut reader = open_maybe_gz(filename)?;
+            let format = match reader.fill_buf() {
+                Err(msg) => return Err(format!("Error with '{}': {}", filename, msg)),
+                Ok(buf) => GenomeFormat::detect(buf),
+            };
+            match format {
+                GenomeFormat::Fasta => {
+                    let fai = if write_fai { Some(format!("{}.fai", filename)) } else { None };
+                    Genome::from_fasta(filename, reader, fai.as_deref())
+                },
+                _ if write_fai => Err(format!("Error with '{}': cannot write a FASTA index for a file that is not FASTA", filename)),
+                GenomeFormat::Sizes => Genome::from_sizes(filename, reader, mode),
+                GenomeFormat::Sam => Genome::from_sam(filename, reader),
+                GenomeFormat::Bam => Genome::from_bam(filename, reader),
+                GenomeFormat::Vcf => Genome::from_vcf(filename, reader),
+            }
+        }
+
+        //a built-in genome, or None if there is no preset with that name
+        pub fn preset(name: &str) -> Option<Result<Genome, String>> {
+            PRESETS.iter().find(|(preset, _)| *preset == name)
+                          .map(|(preset, sizes)| Genome::from_sizes(preset, sizes.as_bytes(), SizesMode::Strict))
+        }
+
+        //write the genome in chrom.sizes format
+        pub fn write_sizes<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+            for (name, size) in self.iter() {
+                writeln!(output, "{}\t{}", name, size)?;
+            }
+            Ok(())
+        }
+
+        fn from_sizes<R: BufRead>(filename: &str, handle: R, mode: SizesMode) -> Result<Genome, String> {
+            let mut genome = Genome::new();
+            let mut lineno = 0;
+            for line in handle.lines() {
+                lineno += 1;
+                match line {
+                    Err(msg) => return Err(format!("Error with '{}': {}", filename, msg)),
+                    Ok(line) => {
+                        let mut cols: Vec<&str> = line.split_whitespace().collect();
+                        if mode == SizesMode::Tolerant {
+                            if cols.is_empty() || cols[0].starts_with('#') {
+                                continue;
+                            }
+                            cols.truncate(2);
+                        }
+                        match cols.len() {
+                            2 => {
+                                match cols[1].parse() {
+                                    Ok(size) => genome.push(cols[0], size).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno, msg))?,
+                                    //a header (e.g. 'chrom\tsize') before the first chromosome
+                                    Err(_) if mode == SizesMode::Tolerant && genome.is_empty() => continue,
+                                    Err(_) => return Err(format!("Error in '{}', line {}: expected unsigned integer, received '{}'", filename, lineno, cols[1])),
+                                };
+                            },
+                            _ => {
+                                return Err(format!("Error in '{}', line {}: expected exactly 2 fields, received {}", filename, lineno, cols.len()))
+                            }
+                        }
+                    }
+                }   
+            }
+            Ok(genome)
+        }
+
+        //read the name and length (the first two columns) of a FASTA index
+        pub fn from_fai(filename: &str) -> Result<Genome, String> {
+            let handle = match File::open(filename) {
+                Err(msg) => return Err(format!("Error with '{}': {}", filename, msg)),
+                Ok(handle) => BufReader::new(handle),
+            };
+            let mut genome = Genome::new();
+            for (lineno, line) in handle.lines().enumerate() {
+                let line = line.map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                let cols: Vec<&str> = line.split('\t').collect();
+                if cols.len() < 2 {
+                    return Err(format!("Error in '{}', line {}: expected at least 2 fields, received {}", filename, lineno + 1, cols.len()));
+                }
+                match cols[1].parse() {
+                    Ok(size) => genome.push(cols[0], size).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno + 1, msg))?,
+                    Err(_) => return Err(format!("Error in '{}', line {}: expected unsigned integer, received '{}'", filename, lineno + 1, cols[1])),
+                };
+            }
+            Ok(genome)
+        }
+
+        //read the @SQ lines of a SAM header
+        fn from_sam<R: BufRead>(filename: &str, handle: R) -> Result<Genome, String> {
+            let mut genome = Genome::new();
+            for (lineno, line) in handle.lines().enumerate() {
+                let line = line.map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                //the header ends at the first alignment
+                if !line.starts_with('@') {
+                    break;
+                }
+                if !line.starts_with("@SQ\t") {
+                    continue;
+                }
+                let name = line.split('\t').find_map(|tag| tag.strip_prefix("SN:"));
+                let size = line.split('\t').find_map(|tag| tag.strip_prefix("LN:"));
+                match (name, size) {
+                    (Some(name), Some(size)) => match size.parse() {
+                        Ok(size) => genome.push(name, size).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno + 1, msg))?,
+                        Err(_) => return Err(format!("Error in '{}', line {}: expected unsigned integer, received '{}'", filename, lineno + 1, size)),
+                    },
+                    _ => return Err(format!("Error in '{}', line {}: expected SN and LN tags in @SQ line", filename, lineno + 1)),
+                };
+            }
+            Ok(genome)
+        }
+
+        //read the reference sequence dictionary of a (decompressed) BAM file
+        fn from_bam<R: BufRead>(filename: &str, mut handle: R) -> Result<Genome, String> {
+            let truncated = |_| format!("Error with '{}': truncated BAM header", filename);
+            let mut word = [0u8; 4];
+            handle.read_exact(&mut word).map_err(truncated)?;
+            //skip the plain text header
+            handle.read_exact(&mut word).map_err(truncated)?;
+            let l_text = u32::from_le_bytes(word) as u64;
+            std::io::copy(&mut handle.by_ref().take(l_text), &mut std::io::sink()).map_err(truncated)?;
+            handle.read_exact(&mut word).map_err(truncated)?;
+            let n_ref = u32::from_le_bytes(word);
+            let mut genome = Genome::new();
+            for _ in 0..n_ref {
+                handle.read_exact(&mut word).map_err(truncated)?;
+                let l_name = u32::from_le_bytes(word) as usize;
+                if l_name > MAX_BAM_NAME {
+                    return Err(format!("Error with '{}': reference name of {} bytes in BAM header (the limit is {})", filename, l_name, MAX_BAM_NAME));
+                }
+                let mut name = vec![0u8; l_name];
+                handle.read_exact(&mut name).map_err(truncated)?;
+                //the name is NUL terminated
+                let name = String::from_utf8_lossy(name.split(|b| *b == 0).next().unwrap_or(&[]));
+                handle.read_exact(&mut word).map_err(truncated)?;
+                genome.push(&name, Coord::from(u32::from_le_bytes(word))).map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+            }
+            Ok(genome)
+        }
+
+        //read the ##contig lines of a VCF header
+        fn from_vcf<R: BufRead>(filename: &str, handle: R) -> Result<Genome, String> {
+            let mut genome = Genome::new();
+            for (lineno, line) in handle.lines().enumerate() {
+                let line = line.map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                //the meta-information ends at the '#CHROM' line
+                if !line.starts_with("##") {
+                    break;
+                }
+                let fields = match line.strip_prefix("##contig=<").and_then(|x| x.strip_suffix('>')) {
+                    Some(fields) => vcf_fields(fields),
+                    None => continue,
+                };
+                let name = fields.iter().find(|(key, _)| *key == "ID").map(|(_, value)| *value);
+                let size = fields.iter().find(|(key, _)| *key == "length").map(|(_, value)| *value);
+                match (name, size) {
+                    (Some(name), Some(size)) => match size.parse() {
+                        Ok(size) => genome.push(name, size).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno + 1, msg))?,
+                        Err(_) => return Err(format!("Error in '{}', line {}: expected unsigned integer, received '{}'", filename, lineno + 1, size)),
+                    },
+                    (Some(name), None) => return Err(format!("Error in '{}', line {}: contig '{}' has no length", filename, lineno + 1, name)),
+                    _ => return Err(format!("Error in '{}', line {}: expected ID in ##contig line", filename, lineno + 1)),
+                };
+            }
+            Ok(genome)
+        }
+
+        //compute sequence lengths by scanning a FASTA file
+        //if fai is given, an index is written there as well
+        fn from_fasta<R: BufRead>(filename: &str, mut handle: R, fai: Option<&str>) -> Result<Genome, String> {
+            let mut genome = Genome::new();
+            let mut index = Vec::new();
+            let mut record: Option<FaiRecord> = None;
+            let mut line = Vec::new();
+            let mut offset: u64 = 0;
+            let mut lineno = 0;
+            loop {
+                line.clear();
+                let bytes = handle.read_until(b'\n', &mut line).map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                if bytes == 0 {
+                    break;
+                }
+                lineno += 1;
+                offset += bytes as u64;
+                if line[0] == b'>' {
+                    if let Some(rec) = record.take() {
+                        genome.push(&rec.name, rec.length).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno, msg))?;
+                        index.push(rec);
+                    }
+                    let header = String::from_utf8_lossy(&line[1..]);
+                    let name = header.split_whitespace().next().unwrap_or("").to_string();
+                    if name.is_empty() {
+                        return Err(format!("Error in '{}', line {}: expected sequence name after '>'", filename, lineno));
+                    }
+                    record = Some(FaiRecord{name, length: 0, offset, line_bases: 0, line_width: 0, ended: false, blank: None});
+                } else {
+                    let rec = match record.as_mut() {
+                        Some(rec) => rec,
+                        None => return Err(format!("Error in '{}', line {}: expected '>' before sequence data", filename, lineno)),
+                    };
+                    let width = line.len() as u64;
+                    let bases = line.iter().filter(|b| !b.is_ascii_whitespace()).count() as u64;
+                    if bases == 0 {
+                        rec.blank = rec.blank.or(Some(lineno));
+                        continue;
+                    }
+                    if fai.is_some() {
+                        if let Some(blank) = rec.blank {
+                            return Err(format!("Error in '{}', line {}: blank line in sequence '{}'", filename, blank, rec.name));
+                        }
+                        //every line except the last one must have the same length
+                        if rec.ended || (rec.line_width > 0 && (width > rec.line_width || bases > rec.line_bases)) {
+                            return Err(format!("Error in '{}', line {}: different line length in sequence '{}'", filename, lineno, rec.name));
+                        }
+                        if rec.line_width > 0 && (width < rec.line_width || bases < rec.line_bases) {
+                            rec.ended = true;
+                        }
+                    }
+                    if rec.line_width == 0 {
+                        rec.line_width = width;
+                        rec.line_bases = bases;
+                    }
+                    rec.length = match rec.length.checked_add(bases as Coord) {
+                        Some(length) => length,
+                        None => return Err(format!("Error in '{}', line {}: sequence '{}' is too long", filename, lineno, rec.name)),
+                    };
+                }
+            }
+            if let Some(rec) = record.take() {
+                genome.push(&rec.name, rec.length).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno, msg))?;
+                index.push(rec);
+            }
+            if let Some(fai) = fai {
+                write_fai(fai, &index)?;
+            }
+            Ok(genome)
+        }
+
+        //add a chromosome to the end of the genome, returning its ID
+        pub fn push(&mut self, name: &str, size: Coord) -> Result<usize, String> {
+            if self.ids.contains_key(name) {
+                return Err(format!("duplicate chromosome '{}'", name));
+            }
+            let id = self.names.len();
+            self.names.push(name.to_string());
+            self.sizes.push(size);
+            self.ids.insert(name.to_string(), id);
+            Ok(id)
+        }
+
+        pub fn len(&self) -> usize {
+            self.names.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.names.is_empty()
+        }
+
+        //look up a chromosome by its name, or by one of its aliases
+        pub fn id(&self, name: &str) -> Option<usize> {
+            self.ids.get(name).or_else(|| self.aliases.get(name)).copied()
+        }
+
+        //allow chromosomes to be looked up by any of their aliases
+        pub fn with_aliases(mut self, table: &AliasTable) -> Genome {
+            for (id, name) in self.names.iter().enumerate() {
+                for alias in table.aliases(name) {
+                    if !self.ids.contains_key(alias) {
+                        self.aliases.insert(alias.to_string(), id);
+                    }
+                }
+            }
+            self
+        }
+
+        //rename the chromosomes into the renamer's naming convention
+        //the old names are kept as aliases
+        pub fn renamed(&self, renamer: &Renamer) -> Result<Genome, String> {
+            let mut genome = Genome::new();
+            for (name, size) in self.iter() {
+                genome.push(&renamer.rename(name), size)?;
+            }
+            for (name, id) in self.ids.iter().chain(self.aliases.iter()) {
+                if !genome.ids.contains_key(name) {
+                    genome.aliases.insert(name.clone(), *id);
+                }
+            }
+            Ok(genome)
+        }
+
+        //panics if the ID is out of range
+        pub fn name(&self, id: usize) -> &str {
+            &self.names[id]
+        }
+
+        //panics if the ID is out of range
+        pub fn size(&self, id: usize) -> Coord {
+            self.sizes[id]
+        }
+
+        pub fn size_of(&self, name: &str) -> Option<Coord> {
+            self.id(name).map(|id| self.sizes[id])
+        }
+
+        //sum of all chromosome sizes
+        pub fn total_len(&self) -> u64 {
+            self.sizes.iter().sum()
+        }
+
+        //chromosome names and sizes, in order
+        pub fn iter(&self) -> impl Iterator<Item = (&str, Coord)> {
+            self.names.iter().map(|name| name.as_str()).zip(self.sizes.iter().copied())
+        }
+    }
+
+    //how strictly to parse a chrom.sizes file
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SizesMode {
+        //every line must have exactly 2 fields
+        Strict,
+        //skip blank lines, '#' comments and a header line before the first
+        //chromosome, and ignore any columns after the first two (e.g. a .fai
+        //file, or a hand-edited chrom.sizes file with notes)
+        Tolerant,
+    }
+
+    //the order of the chromosomes in sorted files
+    //within a chromosome, lines are sorted by start, then stop
+    #[derive(Debug, Clone)]
+    pub enum ChromOrder {
+        //byte order, as 'sort -k1,1'
+        Lexicographic,
+        //runs of digits are compared by value, so chr2 comes before chr10
+        Natural,
+        //the order of the chromosomes in a genome file
+        Genome(Rc<Genome>),
+    }
+
+    impl ChromOrder {
+        pub fn compare_chroms(&self, a: &str, b: &str) -> Ordering {
+            match self {
+                ChromOrder::Lexicographic => a.cmp(b),
+                ChromOrder::Natural => natural_cmp(a, b),
+                //chromosomes missing from the genome come last, in byte order,
+                //so that two of them are never taken for the same one (the
+                //readers reject them, see rank())
+                ChromOrder::Genome(genome) => match (genome.id(a), genome.id(b)) {
+                    (Some(a_id), Some(b_id)) => a_id.cmp(&b_id),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => a.cmp(b),
+                },
+            }
+        }
+
+        //the position of a chromosome in the genome, or an error if the
+        //genome does not have it (0 for the orders that have no genome)
+        pub(crate) fn rank(&self, chrom: &str) -> Result<usize, String> {
+            match self {
+                ChromOrder::Genome(genome) => genome.id(chrom).ok_or_else(|| format!("unknown chromosome '{}'", chrom)),
+                _ => Ok(0),
+            }
+        }
+
+        pub fn compare_segs(&self, a: &ChromSeg, b: &ChromSeg) -> Ordering {
+            self.compare_chroms(&a.chrom, &b.chrom)
+                .then(a.start.cmp(&b.start))
+                .then(a.stop.cmp(&b.stop))
+        }
+    }
+
+    //compare two strings, treating each run of digits as a number
+    //(ties, such as 'chr01' and 'chr1', are broken by byte order)
+    pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+        let (a_bytes, b_bytes) = (a.as_bytes(), b.as_bytes());
+        let (mut i, mut j) = (0, 0);
+        while i < a_bytes.len() && j < b_bytes.len() {
+            if a_bytes[i].is_ascii_digit() && b_bytes[j].is_ascii_digit() {
+                let a_end = i + a_bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_end = j + b_bytes[j..].iter().take_while(|c| c.is_ascii_digit()).count();
+                //strip leading zeros, then a longer number is larger
+                let a_num = a[i..a_end].trim_start_matches('0');
+                let b_num = b[j..b_end].trim_start_matches('0');
+                let ord = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                i = a_end;
+                j = b_end;
+            } else {
+                if a_bytes[i] != b_bytes[j] {
+                    return a_bytes[i].cmp(&b_bytes[j]);
+                }
+                i += 1;
+                j += 1;
+            }
+        }
+        (a_bytes.len() - i).cmp(&(b_bytes.len() - j)).then_with(|| a.cmp(b))
+    }
+
+    //what to do with an interval that is not within the genome
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OutOfBounds {
+        //stop with an error
+        Reject,
+        //shorten the interval to the end of the chromosome
+        //(intervals on unknown chromosomes are dropped)
+        Clip,
+        //skip the interval
+        Drop,
+    }
+
+    impl OutOfBounds {
+        pub fn parse(mode: &str) -> Result<OutOfBounds, String> {
+            match mode {
+                "reject" => Ok(OutOfBounds::Reject),
+                "clip" => Ok(OutOfBounds::Clip),
+                "drop" => Ok(OutOfBounds::Drop),
+                _ => Err(format!("Invalid bounds mode '{}'. Expected one of 'reject', 'clip', 'drop'", mode)),
+            }
+        }
+    }
+
+    //Checks that intervals lie on a known chromosome and within its size,
+    //keeping count of the intervals that had to be clipped or dropped.
+    #[derive(Debug)]
+    pub struct BoundsCheck {
+        genome: Rc<Genome>,
+        mode: OutOfBounds,
+        clipped: u64,
+        dropped: u64,
+    }
+
+    impl BoundsCheck {
+        pub fn new(genome: Rc<Genome>, mode: OutOfBounds) -> BoundsCheck {
+            BoundsCheck{genome, mode, clipped: 0, dropped: 0}
+        }
+
+        //returns Ok(true) if the interval should be kept (it may have been clipped),
+        //Ok(false) if it should be dropped, or Err if it is rejected
+        pub fn check(&mut self, seg: &mut ChromSeg) -> Result<bool, String> {
+            let size = match self.genome.id(&seg.chrom) {
+                Some(id) => self.genome.size(id),
+                None => {
+                    if self.mode == OutOfBounds::Reject {
+                        return Err(format!("unknown chromosome '{}'", seg.chrom));
+                    }
+                    self.dropped += 1;
+                    return Ok(false);
+                },
+            };
+            if seg.stop <= size {
+                return Ok(true);
+            }
+            match self.mode {
+                OutOfBounds::Reject => Err(format!("interval {}:{}-{} extends past the end of the chromosome ({})", seg.chrom, seg.start, seg.stop, size)),
+                OutOfBounds::Clip if seg.start < size => {
+                    seg.stop = size;
+                    self.clipped += 1;
+                    Ok(true)
+                },
+                _ => {
+                    self.dropped += 1;
+                    Ok(false)
+                },
+            }
+        }
+
+        pub fn clipped(&self) -> u64 {
+            self.clipped
+        }
+
+        pub fn dropped(&self) -> u64 {
+            self.dropped
+        }
+
+        //print a summary of the clipped and dropped intervals, if there were any
+        pub fn report(&self, source: &str) {
+            if self.clipped > 0 || self.dropped > 0 {
+                eprintln!("Warning: '{}' had {} intervals outside the genome ({} clipped, {} dropped)", source, self.clipped + self.dropped, self.clipped, self.dropped);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test_chrom_sizes {
+        use super::*;
+
+        #[test]
+        fn natural_order() {
+            assert_eq!(natural_cmp("chr2", "chr10"), Ordering::Less);
+            assert_eq!(natural_cmp("chr10", "chr10_alt"), Ordering::Less);
+            assert_eq!(natural_cmp("chr9", "chrX"), Ordering::Less);
+            assert_eq!(natural_cmp("chr01", "chr1"), Ordering::Less);
+            assert_eq!(natural_cmp("chr1", "chr1"), Ordering::Equal);
+            let mut chroms = vec!["chr10", "chrM", "chr1", "chr2", "chr22", "chr3"];
+            chroms.sort_by(|a, b| natural_cmp(a, b));
+            assert_eq!(chroms, vec!["chr1", "chr2", "chr3", "chr10", "chr22", "chrM"]);
+        }
+
+        #[test]
+        fn test_hg38_chrom_sizes() {
+            let hg38 = Genome::from_file("test/chrom.sizes/hg38.chrom.sizes").unwrap();
+            let pairs: Vec<(&str, Coord)> = vec![("chr1", 248956422),
+                                               ("chr2", 242193529),
+                                               ("chrX", 156040895),
+                                               ("chr19", 58617616),
+                                               ("chr19_GL383573v1_alt", 385657),
+                                               ("chrUn_KI270580v1", 1553),
+                                               ("chrUn_KI270394v1", 970)];
+            for (chrom, size) in pairs {
+                assert_eq!(hg38.size_of(chrom).unwrap(), size);
+            }
+            
+        }
+
+        #[test]
+        fn test_tair10_chrom_sizes() {
+            let tair10 = Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap();
+            let pairs: Vec<(&str, Coord)> = vec![("Chr1", 30427671),
+                                               ("Chr2", 19698289),
+                                               ("Chr3", 23459830),
+                                               ("Chr4", 18585056),
+                                               ("Chr5", 26975502),
+                                               ("ChrC", 154478),
+                                               ("ChrM", 366924),];
+            for (chrom, size) in pairs {
+                assert_eq!(tair10.size_of(chrom).unwrap(), size);
+            }
+        }
+
+        #[test]
+        fn test_large_chrom_sizes() {
+            let genome = Genome::from_file("test/chrom.sizes/large.chrom.sizes").unwrap();
+            assert_eq!(genome.size_of("chr1p"), Some(5026069487));
+            assert_eq!(genome.size_of("chr2p"), Some(4000000000));
+            assert_eq!(genome.total_len(), 5026069487 + 4000000000 + 16372);
+        }
+
+        #[test]
+        fn test_genome_order() {
+            let tair10 = Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap();
+            let names: Vec<&str> = tair10.iter().map(|(name, _)| name).collect();
+            assert_eq!(names, vec!["Chr1", "Chr2", "Chr3", "Chr4", "Chr5", "ChrC", "ChrM"]);
+            assert_eq!(tair10.len(), 7);
+            assert_eq!(tair10.id("Chr3"), Some(2));
+            assert_eq!(tair10.id("chr3"), None);
+            assert_eq!(tair10.name(5), "ChrC");
+            assert_eq!(tair10.size(6), 366924);
+            assert_eq!(tair10.total_len(), 30427671 + 19698289 + 23459830 + 18585056 + 26975502 + 154478 + 366924);
+        }
+
+        fn check_small_fasta(genome: &Genome) {
+            let pairs: Vec<(&str, Coord)> = genome.iter().collect();
+            assert_eq!(pairs, vec![("chrA", 23), ("chrB", 15)]);
+        }
+
+        #[test]
+        fn test_load_formats() {
+            check_small_fasta(&Genome::load("test/fasta/small.fa", false, SizesMode::Strict).unwrap());
+            check_small_fasta(&Genome::load("test/fasta/small.fa.gz", false, SizesMode::Strict).unwrap());
+            check_small_fasta(&Genome::load("test/fasta/small.fa.fai", false, SizesMode::Strict).unwrap());
+            let tair10 = Genome::load("test/chrom.sizes/tair10.chrom.sizes", false, SizesMode::Strict).unwrap();
+            assert_eq!(tair10, Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap());
+        }
+
+        #[test]
+        fn test_load_headers() {
+            check_small_fasta(&Genome::load("test/headers/small.sam", false, SizesMode::Strict).unwrap());
+            check_small_fasta(&Genome::load("test/headers/small.bam", false, SizesMode::Strict).unwrap());
+            check_small_fasta(&Genome::load("test/headers/small.vcf", false, SizesMode::Strict).unwrap());
+            let expect = String::from("Error in 'test/headers/no_length.vcf', line 2: contig 'chrA' has no length");
+            if let Err(msg) = Genome::load("test/headers/no_length.vcf", false, SizesMode::Strict) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::load(), received Ok(_) instead");
+            }
+            let expect = String::from("Error with 'test/headers/long_name.bam': reference name of 4294967280 bytes in BAM header (the limit is 1048576)");
+            if let Err(msg) = Genome::load("test/headers/long_name.bam", false, SizesMode::Strict) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::load(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_presets() {
+            for (name, _) in PRESETS {
+                let genome = Genome::preset(name).unwrap().unwrap();
+                assert!(!genome.is_empty(), "preset '{}' is empty", name);
+            }
+            let hg38 = Genome::load("hg38", false, SizesMode::Strict).unwrap();
+            assert_eq!(hg38, Genome::from_file("test/chrom.sizes/hg38.chrom.sizes").unwrap());
+            assert_eq!(Genome::load("hg19", false, SizesMode::Strict).unwrap().size_of("chr1"), Some(249250621));
+            assert_eq!(Genome::load("sacCer3", false, SizesMode::Strict).unwrap().total_len(), 12157105);
+            assert!(Genome::preset("hg37").is_none());
+            //a file path still takes precedence
+            let tair10 = Genome::load("test/chrom.sizes/tair10.chrom.sizes", false, SizesMode::Strict).unwrap();
+            assert_eq!(tair10.size_of("ChrM"), Some(366924));
+        }
+
+        #[test]
+        fn test_write_sizes() {
+            let genome = Genome::load("test/headers/small.vcf", false, SizesMode::Strict).unwrap();
+            let mut output = Vec::new();
+            genome.write_sizes(&mut output).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "chrA\t23\nchrB\t15\n");
+        }
+
+        #[test]
+        fn test_write_fai() {
+            let dir = std::env::temp_dir().join(format!("ironbed-test-fai-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let fasta = dir.join("small.fa");
+            std::fs::copy("test/fasta/small.fa", &fasta).unwrap();
+            check_small_fasta(&Genome::load(fasta.to_str().unwrap(), true, SizesMode::Strict).unwrap());
+            let actual = std::fs::read_to_string(dir.join("small.fa.fai")).unwrap();
+            let expected = std::fs::read_to_string("test/fasta/small.fa.fai").unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(actual, expected);
+        }
+
+        #[test]
+        fn test_fasta_line_lengths() {
+            //line lengths only matter when writing an index
+            let genome = Genome::load("test/fasta/bad_lines.fa", false, SizesMode::Strict).unwrap();
+            assert_eq!(genome.size_of("chrA"), Some(23));
+            let expect = String::from("Error in 'test/fasta/bad_lines.fa', line 4: different line length in sequence 'chrA'");
+            if let Err(msg) = Genome::load("test/fasta/bad_lines.fa", true, SizesMode::Strict) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::load(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_fai_refused() {
+            let expect = String::from("Error in 'test/fasta/blank_line.fa', line 3: blank line in sequence 'chrA'");
+            if let Err(msg) = Genome::load("test/fasta/blank_line.fa", true, SizesMode::Strict) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::load(), received Ok(_) instead");
+            }
+            //blank lines at the end of a sequence are fine
+            let genome = Genome::load("test/fasta/blank_line.fa", false, SizesMode::Strict).unwrap();
+            assert_eq!(genome.size_of("chrA"), Some(23));
+            assert_eq!(genome.size_of("chrB"), Some(4));
+            let expect = String::from("Error with 'test/fasta/small.fa.gz': cannot write a FASTA index for a compressed file (use 'samtools faidx' to index BGZF files)");
+            if let Err(msg) = Genome::load("test/fasta/small.fa.gz", true, SizesMode::Strict) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::load(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_genome_aliases() {
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let hg38 = Genome::from_file("test/chrom.sizes/hg38-short.chrom.sizes").unwrap().with_aliases(&table);
+            assert_eq!(hg38.size_of("1"), Some(248956422));
+            assert_eq!(hg38.size_of("NC_000001.11"), hg38.size_of("chr1"));
+            let renamer = Renamer::new(table, "refseq").unwrap();
+            let renamed = hg38.renamed(&renamer).unwrap();
+            assert_eq!(renamed.name(0), "NC_000001.11");
+            assert_eq!(renamed.id("chr1"), Some(0));
+            assert_eq!(renamed.len(), hg38.len());
+        }
+
+        #[test]
+        fn test_bounds_check() {
+            let genome = Rc::new(Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap());
+            let seg = |chrom: &str, start, stop| ChromSeg{chrom: chrom.to_string(), start, stop};
+            let mut bounds = BoundsCheck::new(genome.clone(), OutOfBounds::Reject);
+            assert_eq!(bounds.check(&mut seg("ChrC", 0, 154478)), Ok(true));
+            assert_eq!(bounds.check(&mut seg("ChrC", 0, 154479)), Err("interval ChrC:0-154479 extends past the end of the chromosome (154478)".to_string()));
+            assert_eq!(bounds.check(&mut seg("chrC", 0, 100)), Err("unknown chromosome 'chrC'".to_string()));
+            let mut bounds = BoundsCheck::new(genome.clone(), OutOfBounds::Clip);
+            let mut clipped = seg("ChrC", 154000, 155000);
+            assert_eq!(bounds.check(&mut clipped), Ok(true));
+            assert_eq!(clipped, seg("ChrC", 154000, 154478));
+            assert_eq!(bounds.check(&mut seg("ChrC", 155000, 156000)), Ok(false));
+            assert_eq!(bounds.check(&mut seg("chrC", 0, 100)), Ok(false));
+            assert_eq!((bounds.clipped(), bounds.dropped()), (1, 2));
+            let mut bounds = BoundsCheck::new(genome, OutOfBounds::Drop);
+            assert_eq!(bounds.check(&mut seg("ChrC", 154000, 155000)), Ok(false));
+            assert_eq!((bounds.clipped(), bounds.dropped()), (0, 1));
+        }
+
+        #[test]
+        fn test_tolerant_chrom_sizes() {
+            let tolerant = Genome::from_file_mode("test/chrom.sizes/tolerant.chrom.sizes", SizesMode::Tolerant).unwrap();
+            assert_eq!(tolerant, Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap());
+            let loaded = Genome::load("test/chrom.sizes/tolerant.chrom.sizes", false, SizesMode::Tolerant).unwrap();
+            assert_eq!(tolerant, loaded);
+            let header = Genome::from_file_mode("test/chrom.sizes/header.chrom.sizes", SizesMode::Tolerant).unwrap();
+            assert_eq!(tolerant, header);
+            //the strict mode still rejects the same file
+            let expect = String::from("Error in 'test/chrom.sizes/tolerant.chrom.sizes', line 1: expected exactly 2 fields, received 4");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/tolerant.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_tolerant_bad_size() {
+            let expect = String::from("Error in 'test/chrom.sizes/bad_size3.chrom.sizes', line 3: expected unsigned integer, received 'plenty'");
+            if let Err(msg) = Genome::from_file_mode("test/chrom.sizes/bad_size3.chrom.sizes", SizesMode::Tolerant) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file_mode(), received Ok(_) instead");
+            }
+            let expect = String::from("Error in 'test/chrom.sizes/bad_field1.chrom.sizes', line 6: expected exactly 2 fields, received 1");
+            if let Err(msg) = Genome::from_file_mode("test/chrom.sizes/bad_field1.chrom.sizes", SizesMode::Tolerant) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file_mode(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_duplicate_chrom_sizes() {
+            let expect = String::from("Error in 'test/chrom.sizes/duplicate.chrom.sizes', line 4: duplicate chromosome 'Chr2'");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/duplicate.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn chrom_sizes_not_exist() {
+            let expect = String::from("Error with 'test/chrom.sizes/does_not_exist': No such file or directory (os error 2)");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/does_not_exist") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_badfield1_chrom_sizes() {
+            let expect = String::from("Error in 'test/chrom.sizes/bad_field1.chrom.sizes', line 6: expected exactly 2 fields, received 1");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/bad_field1.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_badfield2_chrom_sizes() {
+            let expect = String::from("Error in 'test/chrom.sizes/bad_field2.chrom.sizes', line 3: expected exactly 2 fields, received 3");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/bad_field2.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_badsize1_chrom_sizes() {
+            let expect = String::from("Error in 'test/chrom.sizes/bad_size1.chrom.sizes', line 2: expected unsigned integer, received '-19698289'");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/bad_size1.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn test_badsize2_chrom_sizes() {
+            let expect = String::from("Error in 'test/chrom.sizes/bad_size2.chrom.sizes', line 4: expected unsigned integer, received 'apple'");
+            if let Err(msg) = Genome::from_file("test/chrom.sizes/bad_size2.chrom.sizes") {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from Genome::from_file(), received Ok(_) instead");
+            }
+        }
+    }
+}
+
+pub mod chrom_alias {
+    use std::borrow::Cow;
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+    use std::collections::{HashMap};
+
+    //A table of equivalent chromosome names in different naming conventions
+    //(e.g. 'chr1', '1' and 'NC_000001.11' for UCSC, Ensembl and RefSeq).
+    //Two file layouts are supported, as distributed by UCSC:
+    // - chromAlias.txt, with a header line naming each convention
+    //   (e.g. '# ucsc	assembly	genbank	refseq') and one chromosome per line
+    // - the older chromAlias table, with lines of 'alias	ucsc_name	sources',
+    //   where sources is a comma-separated list of conventions
+    #[derive(Debug, Default)]
+    pub struct AliasTable {
+        conventions: Vec<String>,
+        //for each chromosome, its name in each convention (or "" if it has none)
+        groups: Vec<Vec<String>>,
+        //any name to the chromosome with that name
+        lookup: HashMap<String, usize>,
+    }
+
+    impl AliasTable {
+        pub fn from_file(filename: &str) -> Result<AliasTable, String> {
+            let handle = match File::open(filename) {
+                Err(msg) => return Err(format!("Error with '{}': {}", filename, msg)),
+                Ok(handle) => BufReader::new(handle),
+            };
+            let mut table = AliasTable::default();
+            let mut header = false;
+            for (lineno, line) in handle.lines().enumerate() {
+                let line = line.map_err(|msg| format!("Error with '{}': {}", filename, msg))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if lineno == 0 && line.starts_with('#') {
+                    header = true;
+                    table.conventions = line.trim_start_matches('#').split('\t').map(|x| x.trim().to_string()).collect();
+                    continue;
+                }
+                let cols: Vec<&str> = line.split('\t').map(|x| x.trim()).collect();
+                if header {
+                    if cols.len() > table.conventions.len() {
+                        return Err(format!("Error in '{}', line {}: expected at most {} fields, received {}", filename, lineno + 1, table.conventions.len(), cols.len()));
+                    }
+                    let mut names: Vec<String> = cols.iter().map(|x| x.to_string()).collect();
+                    names.resize(table.conventions.len(), String::new());
+                    table.add_group(names).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno + 1, msg))?;
+                } else {
+                    if cols.len() != 3 {
+                        return Err(format!("Error in '{}', line {}: expected exactly 3 fields, received {}", filename, lineno + 1, cols.len()));
+                    }
+                    table.add_legacy(cols[0], cols[1], cols[2]).map_err(|msg| format!("Error in '{}', line {}: {}", filename, lineno + 1, msg))?;
+                }
+            }
+            Ok(table)
+        }
+
+        fn add_group(&mut self, names: Vec<String>) -> Result<(), String> {
+            let id = self.groups.len();
+            for name in names.iter().filter(|name| !name.is_empty()) {
+                match self.lookup.get(name) {
+                    Some(other) if *other != id => return Err(format!("'{}' is an alias of more than one chromosome", name)),
+                    _ => self.lookup.insert(name.clone(), id),
+                };
+            }
+            self.groups.push(names);
+            Ok(())
+        }
+
+        fn convention_or_insert(&mut self, convention: &str) -> usize {
+            match self.convention(convention) {
+                Some(idx) => idx,
+                None => {
+                    self.conventions.push(convention.to_string());
+                    for group in self.groups.iter_mut() {
+                        group.push(String::new());
+                    }
+                    self.conventions.len() - 1
+                }
+            }
+        }
+
+        //add a line of the older 'alias, ucsc_name, sources' layout
+        fn add_legacy(&mut self, alias: &str, ucsc: &str, sources: &str) -> Result<(), String> {
+            let ucsc_idx = self.convention_or_insert("ucsc");
+            let id = match self.lookup.get(ucsc) {
+                Some(id) => *id,
+                None => {
+                    let mut names = vec![String::new(); self.conventions.len()];
+                    names[ucsc_idx] = ucsc.to_string();
+                    self.add_group(names)?;
+                    self.groups.len() - 1
+                }
+            };
+            for source in sources.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+                let idx = self.convention_or_insert(source);
+                self.groups[id][idx] = alias.to_string();
+            }
+            match self.lookup.get(alias) {
+                Some(other) if *other != id => Err(format!("'{}' is an alias of more than one chromosome", alias)),
+                _ => {
+                    self.lookup.insert(alias.to_string(), id);
+                    Ok(())
+                }
+            }
+        }
+
+        pub fn conventions(&self) -> &[String] {
+            &self.conventions
+        }
+
+        pub fn convention(&self, name: &str) -> Option<usize> {
+            self.conventions.iter().position(|x| x == name)
+        }
+
+        //all names of the chromosome called 'name' (including 'name' itself)
+        pub fn aliases<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> {
+            let group = self.lookup.get(name).map(|id| self.groups[*id].as_slice()).unwrap_or(&[]);
+            group.iter().filter(|x| !x.is_empty()).map(|x| x.as_str())
+        }
+
+        //the name of a chromosome in the given convention, if it has one
+        pub fn name_in(&self, name: &str, convention: usize) -> Option<&str> {
+            let id = self.lookup.get(name)?;
+            let alias = self.groups[*id][convention].as_str();
+            if alias.is_empty() { None } else { Some(alias) }
+        }
+    }
+
+    //Renames chromosomes into a single naming convention, or (without a
+    //convention) to the first name seen for each chromosome, so that
+    //aliases match without changing the names in the input.
+    //Names that are not in the table, or that have no name in the
+    //convention, are left as they are.
+    #[derive(Debug)]
+    pub struct Renamer {
+        table: AliasTable,
+        convention: Option<usize>,
+        //the first name seen in each group of aliases, without a convention
+        first_names: RefCell<HashMap<usize, String>>,
+    }
+
+    impl Renamer {
+        pub fn new(table: AliasTable, convention: &str) -> Result<Renamer, String> {
+            match table.convention(convention) {
+                Some(convention) => Ok( Renamer{table, convention: Some(convention), first_names: RefCell::default()} ),
+                None => Err(format!("Unknown naming convention '{}' (expected one of: {})", convention, table.conventions.join(", "))),
+            }
+        }
+
+        //a renamer that keeps the first name seen for each chromosome
+        pub fn keep_first(table: AliasTable) -> Renamer {
+            Renamer{table, convention: None, first_names: RefCell::default()}
+        }
+
+        pub fn table(&self) -> &AliasTable {
+            &self.table
+        }
+
+        pub fn rename<'a>(&'a self, name: &'a str) -> Cow<'a, str> {
+            let convention = match self.convention {
+                Some(convention) => return Cow::Borrowed(self.table.name_in(name, convention).unwrap_or(name)),
+                None => self.table.lookup.get(name),
+            };
+            match convention {
+                None => Cow::Borrowed(name),
+                Some(id) => {
+                    let mut first_names = self.first_names.borrow_mut();
+                    let first = first_names.entry(*id).or_insert_with(|| name.to_string());
+                    if first == name { Cow::Borrowed(name) } else { Cow::Owned(first.clone()) }
+                },
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test_chrom_alias {
+        use super::*;
+
+        #[test]
+        fn alias_header_layout() {
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            assert_eq!(table.conventions(), &["ucsc", "assembly", "genbank", "ensembl", "refseq"]);
+            let aliases: Vec<&str> = table.aliases("1").collect();
+            assert_eq!(aliases, vec!["chr1", "1", "CM000663.2", "1", "NC_000001.11"]);
+            assert_eq!(table.name_in("NC_000001.11", 0), Some("chr1"));
+            assert_eq!(table.name_in("chrM", 4), Some("NC_012920.1"));
+            //chrUn_KI270302v1 has no Ensembl name
+            assert_eq!(table.name_in("chrUn_KI270302v1", 3), None);
+            assert_eq!(table.aliases("chr99").count(), 0);
+        }
+
+        #[test]
+        fn alias_legacy_layout() {
+            let table = AliasTable::from_file("test/alias/legacy.chromAlias.txt").unwrap();
+            assert_eq!(table.conventions(), &["ucsc", "ensembl", "refseq", "genbank"]);
+            assert_eq!(table.name_in("1", 2), Some("NC_000001.11"));
+            assert_eq!(table.name_in("CM000664.2", 1), Some("2"));
+            assert_eq!(table.name_in("NC_000002.12", 0), Some("chr2"));
+        }
+
+        #[test]
+        fn renamer() {
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let renamer = Renamer::new(table, "ensembl").unwrap();
+            assert_eq!(renamer.rename("chr1"), "1");
+            assert_eq!(renamer.rename("NC_012920.1"), "MT");
+            assert_eq!(renamer.rename("chrUn_KI270302v1"), "chrUn_KI270302v1");
+            assert_eq!(renamer.rename("unknown"), "unknown");
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let renamer = Renamer::keep_first(table);
+            assert_eq!(renamer.rename("1"), "1");
+            assert_eq!(renamer.rename("chr1"), "1");
+            assert_eq!(renamer.rename("NC_012920.1"), "NC_012920.1");
+            assert_eq!(renamer.rename("chrM"), "NC_012920.1");
+            assert_eq!(renamer.rename("unknown"), "unknown");
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let expect = "Unknown naming convention 'gencode' (expected one of: ucsc, assembly, genbank, ensembl, refseq)";
+            match Renamer::new(table, "gencode") {
+                Err(msg) => assert_eq!(msg, expect),
+                Ok(_) => panic!("Expected Err from Renamer::new(), received Ok(_) instead"),
+            }
+        }
+    }
+}
+
+pub mod bedgraph {
+    use super::chrom_geo::{self, Coord};
+    use super::chrom_alias::Renamer;
+    use super::chrom_sizes::BoundsCheck;
+    use super::chrom_sizes::ChromOrder;
+    use std::rc::Rc;
+    use std::fmt;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct BgLine {
+        pub coords: chrom_geo::ChromSeg,
+        //TODO: turn the data into a str that references another field "raw line"
+        pub data: Option<String>,
+    }
+
+    impl BgLine {
+        fn new(input_str: &str) -> Result<BgLine, String> {
+            //TODO: avoid iterating over the entire line by using enumerate
+            let cols: Vec<&str> = input_str.split_whitespace().collect();
+            if cols.len() < 3 {
+                Err(format!("Invalid number of columns [{}] in line:\n{}", cols.len(), input_str))
+            } else {
+                //use lifetimes to make this work, instead of copying the string
+                let chrom = cols[0].to_string();
+                let start: Coord = cols[1].parse().map_err(|_| format!("expected unsigned integer, received '{}'", cols[1]))?;
+                let stop:  Coord = cols[2].parse().map_err(|_| format!("expected unsigned integer, received '{}'", cols[2]))?;
+                if start > stop {
+                    return Err(format!("start ({}) is greater than stop ({})", start, stop));
+                }
+                if cols.len() > 3 {
+                    Ok( BgLine{coords: chrom_geo::ChromSeg{chrom, start, stop}, data: Some(cols[3..].join("\t")) } )
+                } else {
+                    Ok( BgLine{coords: chrom_geo::ChromSeg{chrom, start, stop}, data: None} )
+                }
+            }
+        }
+
+        //TODO: implement this with the new_chrom to minimize string comparison?
+        pub fn starts_after(&self, pos: &chrom_geo::ChromPos) -> bool {
+            self.coords.start_pos() > *pos 
+        }
+
+        pub fn ends_before(&self, pos: &chrom_geo::ChromPos) -> bool {
+            self.coords.stop_pos() <= *pos 
+        }
+
+        //the strand of a BED line (column 6), if it has that many columns
+        pub fn strand(&self) -> Option<&str> {
+            self.data.as_ref().and_then(|data| data.split('\t').nth(2))
+        }
+    }
+
+    impl fmt::Display for BgLine {
+        
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.data {
+                Some(ref data) =>  
+                    write!(f, "{}\t{}\t{}\t{}", self.coords.chrom, self.coords.start, self.coords.stop, data),
+                None =>
+                    write!(f, "{}\t{}\t{}", self.coords.chrom, self.coords.start, self.coords.stop)
+            }
+            
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct BgIterator {
+        reader: BufReader<File>,
+        fname: String,
+        lineno: u32,
+        renamer: Option<Rc<Renamer>>,
+        bounds: Option<BoundsCheck>,
+        //lines already read into memory and sorted by presort()
+        sorted: Option<std::vec::IntoIter<BgLine>>,
+    }
+
+    impl BgIterator {
+        pub fn new(fname: &str) -> Result<BgIterator, String> {
+            //consider removing... this information is in the reader
+            match File::open(fname) {
+                Err(x) => Err(x.to_string()),
+                Ok(handle) =>
+                    Ok( BgIterator{ reader: BufReader::new(handle), fname: fname.to_string(), lineno: 0, renamer: None, bounds: None, sorted: None }   )
+            }
+        }
+
+        //as new(), with the file name in the error, and with the chromosomes
+        //renamed as they are read if a renamer is given
+        pub fn open(fname: &str, renamer: Option<&Rc<Renamer>>) -> Result<BgIterator, String> {
+            let bg = BgIterator::new(fname).map_err(|err| format!("Error with '{}': {}", fname, err))?;
+            Ok(match renamer {
+                Some(renamer) => bg.with_renamer(renamer.clone()),
+                None => bg,
+            })
+        }
+
+        //the number of lines read so far
+        pub(crate) fn lineno(&self) -> u32 {
+            self.lineno
+        }
+
+        //check every line against a genome as it is read
+        pub fn with_bounds(mut self, bounds: BoundsCheck) -> BgIterator {
+            self.bounds = Some(bounds);
+            self
+        }
+
+        //read the next line, returning an error (with the file and line
+        //number) if it cannot be parsed or is rejected by the bounds check
+        pub fn try_next(&mut self) -> Result<Option<BgLine>, String> {
+            if let Some(ref mut sorted) = self.sorted {
+                return Ok(sorted.next());
+            }
+            loop {
+                //TODO: allocate to be the size of the previous line?
+                let mut temp = String::new();
+                let bytes = self.reader.read_line(&mut temp).map_err(|err| format!("Error with '{}': {}", self.fname, err))?;
+                if bytes == 0 {
+                    if let Some(bounds) = self.bounds.take() {
+                        bounds.report(&self.fname);
+                    }
+                    return Ok(None);
+                }
+                self.lineno += 1;
+                let (fname, lineno) = (&self.fname, self.lineno);
+                let context = |msg| format!("Error in '{}', line {}: {}", fname, lineno, msg);
+                let mut line = BgLine::new(&temp).map_err(context)?;
+                if let Some(ref renamer) = self.renamer {
+                    let name = renamer.rename(&line.coords.chrom);
+                    if name != line.coords.chrom {
+                        line.coords.chrom = name.into_owned();
+                    }
+                }
+                let keep = match self.bounds {
+                    Some(ref mut bounds) => bounds.check(&mut line.coords).map_err(context)?,
+                    None => true,
+                };
+                if keep {
+                    return Ok(Some(line));
+                }
+            }
+        }
+
+        //read the rest of the file into memory and sort it by chromosome
+        //(lexicographically, as BgUnion expects), then start, then stop
+        //the memory used is taken from budget, and it is an error to run out
+        pub fn presort(&mut self, budget: &mut usize) -> Result<(), String> {
+            let mut lines = Vec::new();
+            while let Some(line) = self.try_next()? {
+                let size = std::mem::size_of::<BgLine>() + line.coords.chrom.capacity() + line.data.as_ref().map_or(0, |data| data.capacity());
+                if size > *budget {
+                    return Err(format!("Error with '{}': not enough memory to presort the inputs (sort them with 'ironbed sort' first, or raise the memory budget)", self.fname));
+                }
+                *budget -= size;
+                lines.push(line);
+            }
+            lines.sort_by(|a, b| ChromOrder::Lexicographic.compare_segs(&a.coords, &b.coords));
+            self.sorted = Some(lines.into_iter());
+            Ok(())
+        }
+
+        //rename the chromosome of every line as it is read, so that files
+        //with different naming conventions can be compared
+        pub fn with_renamer(mut self, renamer: Rc<Renamer>) -> BgIterator {
+            self.renamer = Some(renamer);
+            self
+        }
+    }
+
+    impl Iterator for BgIterator {
+        type Item = BgLine;
+
+        //errors cannot be passed through the Iterator interface, so they are
+        //reported here and the program exits (use try_next() to handle them)
+        fn next(&mut self) -> Option<Self::Item> {
+            self.try_next().unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            })
+        }
+    }
+    #[cfg(test)]
+    mod test_bedgraph {
+        use super::*;
+        use super::super::chrom_sizes::{Genome, OutOfBounds, SizesMode};
+        
+        //helper functions for checking chrom_seg and data
+        fn check_segment(line: &Option<BgLine>, coords: chrom_geo::ChromSeg) {
+            if let Some(line) = line {
+                assert_eq!(line.coords, coords);
+            } else {
+                panic!("Last line is None: {:?}", line);
+            }
+        }
+
+        fn check_data(line: &Option<BgLine>, data: Option<String>) {
+            if let Some(line) = line {
+                assert_eq!(line.data, data);
+            } else {
+                panic!("Last line is None: {:?}", line);
+            }
+        }
+        
+        #[test]
+        fn starts_after() {
+            let bg = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
+            let pos = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1980};
+            let test_values = bg.map(|x| x.starts_after(&pos)).collect::<Vec<bool>>();
+            let expected_values = vec![false, false, false, false, false, true, true, true, true];
+            assert_eq!(test_values, expected_values);
+            let bg = BgIterator::new("test/unionbedg/long.bg").unwrap();
+            let pos = chrom_geo::ChromPos{chrom: "chr2".to_string(), index: 4000};
+            let test_values = bg.map(|x| x.starts_after(&pos)).collect::<Vec<bool>>();
+            let expected_values = vec![false, false, false, false, true, true, true, true];
+            assert_eq!(test_values, expected_values);
+        }
+
+        #[test]
+        fn ends_before() {
+            let bg = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
+            let pos = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1980};
+            let test_values = bg.map(|x| x.ends_before(&pos)).collect::<Vec<bool>>();
+            let expected_values = vec![true, true, true, true, false, false, false, false, false];
+            assert_eq!(test_values, expected_values);
+            let bg = BgIterator::new("test/unionbedg/long.bg").unwrap();
+            let pos = chrom_geo::ChromPos{chrom: "chr2".to_string(), index: 4000};
+            let test_values = bg.map(|x| x.ends_before(&pos)).collect::<Vec<bool>>();
+            let expected_values = vec![true, true, true, true, false, false, false, false];
+            assert_eq!(test_values, expected_values);
+        }
+
+        #[test]
+        fn large_coords() {
+            let genome = Rc::new(Genome::from_file("test/chrom.sizes/large.chrom.sizes").unwrap());
+            let bounds = BoundsCheck::new(genome, OutOfBounds::Reject);
+            let mut bg = BgIterator::new("test/bedgraph/large.bg").unwrap().with_bounds(bounds);
+            bg.next();
+            let line = bg.next();
+            check_segment(&line, chrom_geo::ChromSeg{chrom: "chr1p".to_string(), start: 4294967290, stop: 4294967400});
+            let line = bg.next();
+            check_segment(&line, chrom_geo::ChromSeg{chrom: "chr1p".to_string(), start: 5026069400, stop: 5026069487});
+            assert!(bg.next().is_none());
+        }
+
+        #[test]
+        fn line_count() {
+            //create a bedgraph iterator for a test file with 9 lines
+            let bedgraph = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
+            //check the number of lines
+            let count = bedgraph.count();
+            assert_eq!(count, 9);
+        }
+
+        #[test]
+        fn iterator_next() {
+            let mut bedgraph = BgIterator::new("test/unionbedg/1.bg").unwrap();
+            let last_line = bedgraph.next();
+            check_segment(&last_line, chrom_geo::ChromSeg{chrom: "chr1".to_string(), start: 1000, stop: 1500 } );
+            check_data(&last_line, Some("10".to_string()));
+            let last_line = bedgraph.next();
+            check_segment(&last_line, chrom_geo::ChromSeg{chrom: "chr1".to_string(), start: 2000, stop: 2100 } );
+            check_data(&last_line, Some("20".to_string()));
+            let last_line = bedgraph.next();
+            assert_eq!(last_line, None);
+        }
+        
+        #[test]
+        fn bounds_reject() {
+            let genome = Rc::new(Genome::load("test/fasta/small.fa.fai", false, SizesMode::Strict).unwrap());
+            let mut bedgraph = BgIterator::new("test/bedgraph/out_of_bounds.bg").unwrap()
+                                          .with_bounds(BoundsCheck::new(genome, OutOfBounds::Reject));
+            let first = bedgraph.try_next().unwrap();
+            check_segment(&first, chrom_geo::ChromSeg{chrom: "chrA".to_string(), start: 0, stop: 10});
+            let expect = "Error in 'test/bedgraph/out_of_bounds.bg', line 2: interval chrA:20-30 extends past the end of the chromosome (23)";
+            assert_eq!(bedgraph.try_next(), Err(expect.to_string()));
+        }
+
+        #[test]
+        fn bounds_clip() {
+            let genome = Rc::new(Genome::load("test/fasta/small.fa.fai", false, SizesMode::Strict).unwrap());
+            let bedgraph = BgIterator::new("test/bedgraph/out_of_bounds.bg").unwrap()
+                                      .with_bounds(BoundsCheck::new(genome, OutOfBounds::Clip));
+            let lines: Vec<String> = bedgraph.map(|line| line.to_string()).collect();
+            assert_eq!(lines, vec!["chrA\t0\t10\t1", "chrA\t20\t23\t2", "chrB\t10\t15\t4"]);
+        }
+
+        #[test]
+        fn parse_error_context() {
+            let mut bedgraph = BgIterator::new("test/unionbedg/bad-tabs.bg").unwrap();
+            let expect = "Error in 'test/unionbedg/bad-tabs.bg', line 1: Invalid number of columns [2] in line:\nchr1\t1000\n";
+            assert_eq!(bedgraph.try_next(), Err(expect.to_string()));
+            let mut bedgraph = BgIterator::new("test/bedgraph/reversed.bg").unwrap();
+            bedgraph.try_next().unwrap();
+            let expect = "Error in 'test/bedgraph/reversed.bg', line 2: start (100) is greater than stop (50)";
+            assert_eq!(bedgraph.try_next(), Err(expect.to_string()));
+        }
+
+        #[test]
+        fn min_iterators() {
+            let bedgraph1 = BgIterator::new("test/unionbedg/1.bg").unwrap();
+            let bedgraph2 = BgIterator::new("test/unionbedg/2.bg").unwrap();
+            let bedgraph3 = BgIterator::new("test/unionbedg/3.bg").unwrap();
+            let mut readers: Vec<BgIterator> = vec![bedgraph1, bedgraph2, bedgraph3];
+            let lines: Vec<BgLine> = readers.iter_mut().map(|x| x.next().unwrap()).collect();
+            let min_start: chrom_geo::ChromPos = lines.iter().map(|x| x.coords.start_pos()).min().unwrap();
+            assert_eq!(min_start, chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 900});
+            let min_start: chrom_geo::ChromPos = lines.iter().map(|x| x.coords.stop_pos()).min().unwrap();
+            assert_eq!(min_start, chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1500});
+        }
+    }
+}
+
+pub mod union {
+    use std::rc::Rc;
+    use super::chrom_geo;
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::chrom_sizes::{BoundsCheck, Genome, OutOfBounds};
+    use super::chrom_alias::Renamer;
+
+    //Each reader can have three states:
+    // In - the current position of the Union interesects with the Reader at BgLine
+    // Out - the current position of the Union does NOT intersect with the Reader at BgLine
+    // Done - the reader has nothing left
+    enum UnionLine {
+        In(BgLine),
+        Out(BgLine),
+        Done
+    }
+
+    #[derive(Debug)]
+    pub struct UnionConfig<'a> {
+        pub report_empty: bool,
+        pub filler: &'a str,
+        pub genome: Option<Rc<Genome>>,
+    }
+
+    pub struct BgUnion<'a> {
+        readers: Vec<BgIterator>,
+        lines: Vec<UnionLine>,
+        curr: chrom_geo::ChromPos,
+        config: UnionConfig<'a>,
+    }
+
+    impl<'a> BgUnion<'a> {
+        pub fn new(readers: Vec<BgIterator>) -> Result<BgUnion<'static>, &'static str> {
+            // simply call the "with_config" method using the default config below
+            let config = UnionConfig{report_empty: false, filler: "0", genome: None};
+            BgUnion::with_config(readers, config)
+        }
+        
+        pub fn with_config(mut readers: Vec<BgIterator>, config: UnionConfig) -> Result<BgUnion, &'static str> {
+            let mut lines: Vec<UnionLine> = Vec::with_capacity(readers.len());
+            for rdr in readers.iter_mut() {
+                match rdr.next() {
+                    Some(bgl) => lines.push(UnionLine::Out(bgl)),
+                    None => lines.push(UnionLine::Done),
+                }
+            }
+            //TODO: add logic to find the first site
+            //remove this hard coding
+            let curr = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 0};
+            Ok( BgUnion{readers, lines, curr, config} )
+        }
+
+        fn next_transition(&self) -> chrom_geo::ChromPos {
+            //TODO: add logic for empty spaces / provided sizes file
+            self.lines.iter().filter_map(
+                | x | match x {
+                        UnionLine::Done => None,
+                        UnionLine::In(ref line) => Some(line.coords.stop_pos()),
+                        UnionLine::Out(ref line) => Some(line.coords.start_pos()),
+            }).min().unwrap()
+        }
+
+        fn advance_lines(&mut self, curr: &chrom_geo::ChromPos) {
+            self.lines = std::mem::take(&mut self.lines).into_iter().zip(self.readers.iter_mut()).map(| (old_line, reader) | {
+                match old_line {
+                    UnionLine::Done => UnionLine::Done,
+                    UnionLine::Out(line_data) => {
+                        // here we assume that if a line is "outside"
+                        // then we either haven't reached it yet, or have, and it is in
+                        if line_data.starts_after(curr) {
+                            UnionLine::Out(line_data)
+                        } else {
+                            // line has been reached!
+                            UnionLine::In(line_data)
+                        }
+                    },
+                    UnionLine::In(line_data) => {
+                        // here we assume that the line has been reached
+                        // thus we will either remain inside it, or move on and get a new line
+                        if line_data.ends_before(curr) {
+                            match reader.next() {
+                                Some(new_line) => {
+                                    if new_line.starts_after(curr) {
+                                        UnionLine::Out(new_line)
+                                    } else {
+                                    // line has been reached!
+                                        UnionLine::In(new_line)
+                                    }
+                                },
+                                //reader has nothing left
+                                None => UnionLine::Done,
+                            }
+                        } else {
+                            UnionLine::In(line_data)
+                        }
+                    },
+                }
+            }).collect();
+        }
+    }
+
+    impl<'a> Iterator for BgUnion<'a> {
+        type Item = BgLine;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            //make this a flag that gets updated automatically
+            if self.lines.iter().all(| x | matches!(x, UnionLine::Done)) {
+                return None;
+            }
+            let next_trans = self.next_transition();
+            let has_in = self.lines.iter().any(| x | matches!(x, UnionLine::In(_)));
+            //prep the data... do this in a better way if possible
+            let formatted_data: String = self.lines.iter().map(| x | {
+                match x { 
+                    UnionLine::In(ref line) => {
+                        match line.data {
+                            Some(ref line) => line,
+                            None => self.config.filler,
+                        }
+                    },
+                    _ => self.config.filler,
+                }}).collect::<Vec<&str>>().join("\t");
+            //advance all the readers / lines based on the next transition
+            self.advance_lines(&next_trans);
+            //swap "curr" with the new transition
+            //next_trans is now the 
+            let old_trans = std::mem::replace(&mut self.curr, next_trans);
+            let coords = chrom_geo::ChromSeg{
+                    chrom: old_trans.chrom,
+                    start: old_trans.index,
+                    stop: self.curr.index
+            };
+            let line = BgLine{coords, data: Some(formatted_data)};
+            // check there are any bedgraph data for this region
+            if has_in {
+                //yield the line
+                Some(line)
+            } else {
+                // otherwise, check if we should yield this
+                // line or not, depending on the settings
+                if self.config.report_empty {
+                    Some(line)
+                } else {
+                   self.next() 
+                }
+            }
+        }
+    }
+
+
+    //if a genome is given, every input line is checked against it,
+    //and lines outside of it are handled according to 'bounds'
+    //if presort is given, every input is read into memory and sorted first,
+    //using at most that many bytes in total
+    pub fn union_main(filenames: Vec<&str>, filler: &str, report_empty: bool, genome: Option<Genome>, renamer: Option<Rc<Renamer>>, bounds: OutOfBounds, presort: Option<usize>) -> Result<(), String> {
+        let genome = genome.map(Rc::new);
+        let mut budget = presort;
+        // open the bedgraph files
+        let mut bg_iters: Vec<BgIterator> = Vec::with_capacity(filenames.len());
+        for fname in filenames {
+            let mut bg_iter = match BgIterator::new(fname) {
+                Ok(bg_iter) => bg_iter,
+                Err(e) => return Err(format!("{}: '{}'", e, fname)),
+            };
+            if let Some(ref renamer) = renamer {
+                bg_iter = bg_iter.with_renamer(renamer.clone());
+            }
+            if let Some(ref genome) = genome {
+                bg_iter = bg_iter.with_bounds(BoundsCheck::new(genome.clone(), bounds));
+            }
+            if let Some(ref mut budget) = budget {
+                bg_iter.presort(budget)?;
+            }
+            bg_iters.push(bg_iter);
+        }
+        //prepare the config
+        let config = UnionConfig{filler, report_empty, genome};
+        let union = BgUnion::with_config(bg_iters, config)?;
+        for line in union {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    
+    #[cfg(test)]
+    mod test_union {
+        use super::*;
+        use super::super::chrom_alias::AliasTable;
+
+        #[test]
+        fn union_defaults() {
+            //gather the correct inputs into a union
+            let inputs: Vec<BgIterator> = ["test/unionbedg/1.bg", 
+                                               "test/unionbedg/2.bg",
+                                                "test/unionbedg/3.bg"].iter()
+                                                                      .map(|name| BgIterator::new(name).unwrap())
+                                                                      .collect();
+            let union = BgUnion::new(inputs).unwrap();
+            let expected_iterator = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
+            for (actual, expected) in union.zip(expected_iterator) {
+                assert_eq!(actual, expected);
+            }
+        }
+        
+        #[test]
+        fn union_presorted() {
+            let mut budget = 1 << 20;
+            let inputs: Vec<BgIterator> = ["test/unionbedg/unsorted-1.bg",
+                                           "test/unionbedg/unsorted-2.bg",
+                                           "test/unionbedg/unsorted-3.bg"].iter()
+                                                                          .map(|name| {
+                                                                              let mut bg = BgIterator::new(name).unwrap();
+                                                                              bg.presort(&mut budget).unwrap();
+                                                                              bg
+                                                                          })
+                                                                          .collect();
+            let union: Vec<BgLine> = BgUnion::new(inputs).unwrap().collect();
+            let expected: Vec<BgLine> = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap().collect();
+            assert_eq!(union, expected);
+        }
+
+        #[test]
+        fn presort_budget() {
+            let mut budget = 100;
+            let mut bg = BgIterator::new("test/unionbedg/unsorted-1.bg").unwrap();
+            let expect = String::from("Error with 'test/unionbedg/unsorted-1.bg': not enough memory to presort the inputs (sort them with 'ironbed sort' first, or raise the memory budget)");
+            if let Err(msg) = bg.presort(&mut budget) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from presort(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn union_defaults2() {
+            //gather the correct inputs into a union
+            let inputs: Vec<BgIterator> = ["test/unionbedg/empty-1.bg",
+                                               "test/unionbedg/empty-2.bg"].iter()
+                                                            .map(|name| BgIterator::new(name).unwrap())
+                                                            .collect();
+            let union = BgUnion::new(inputs).unwrap();
+            let expected_iterator = BgIterator::new("test/unionbedg/empty-1+2.bg").unwrap();
+            for (actual, expected) in union.zip(expected_iterator) {
+                assert_eq!(actual, expected);
+            }
+        }
+
+        #[test]
+        fn union_filler1() {
+            let inputs: Vec<BgIterator> = ["test/unionbedg/1.bg", 
+                                               "test/unionbedg/2.bg",
+                                                "test/unionbedg/3.bg"].iter()
+                                                                      .map(|name| BgIterator::new(name).unwrap())
+                                                                      .collect();
+            let config = UnionConfig{report_empty: false, filler: "NA", genome: None};
+            let union = BgUnion::with_config(inputs, config).unwrap();
+            let expected_iterator = BgIterator::new("test/unionbedg/1+2+3.NA-filling.bg").unwrap();
+            for (actual, expected) in union.zip(expected_iterator) {
+                assert_eq!(actual, expected);
+            }
+        }
+        
+        #[test]
+        fn union_renamed() {
+            //1.bg with Ensembl names, combined with files using UCSC names
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let renamer = Rc::new(Renamer::new(table, "ucsc").unwrap());
+            let inputs: Vec<BgIterator> = ["test/alias/1.ensembl.bg",
+                                           "test/unionbedg/2.bg",
+                                           "test/unionbedg/3.bg"].iter()
+                                                                  .map(|name| BgIterator::new(name).unwrap().with_renamer(renamer.clone()))
+                                                                  .collect();
+            let union = BgUnion::new(inputs).unwrap();
+            let expected_iterator = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
+            for (actual, expected) in union.zip(expected_iterator) {
+                assert_eq!(actual, expected);
+            }
+        }
+
+        #[test]
+        fn union_filler2() {
+            //gather the correct inputs into a union
+            let inputs: Vec<BgIterator> = ["test/unionbedg/empty-1.bg",
+                                               "test/unionbedg/empty-2.bg"].iter()
+                                                            .map(|name| BgIterator::new(name).unwrap())
+                                                            .collect();
+            let config = UnionConfig{report_empty: false, filler: "apple", genome: None};
+            let union = BgUnion::with_config(inputs, config).unwrap();
+            let expected_iterator = BgIterator::new("test/unionbedg/empty-1+2.apple-filling.bg").unwrap();
+            for (actual, expected) in union.zip(expected_iterator) {
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+}
+pub mod sort {
+    use std::cmp::Ordering;
+    use std::collections::BinaryHeap;
+    use std::fs::{self, File};
+    use std::io::{BufRead, BufReader, BufWriter, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::collections::HashSet;
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::chrom_sizes::{self, BoundsCheck, ChromOrder, OutOfBounds};
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use std::rc::Rc;
+
+    //the parts of the chromosome order that only sorting needs
+    fn compare_records(order: &ChromOrder, a: &SortRecord, b: &SortRecord) -> Ordering {
+        let chroms = match order {
+            //aliases of the same chromosome have the same rank
+            ChromOrder::Genome(_) => a.rank.cmp(&b.rank),
+            _ => order.compare_chroms(a.chrom(), b.chrom()),
+        };
+        chroms.then(a.start.cmp(&b.start)).then(a.stop.cmp(&b.stop))
+    }
+
+    //parse a memory size such as '500M' or '2G' (K, M and G are powers of 1024)
+    pub fn parse_mem_size(size: &str) -> Result<usize, String> {
+        let upper = size.trim().to_ascii_uppercase();
+        let digits = upper.trim_end_matches('B');
+        let (digits, scale) = match digits.chars().last() {
+            Some('K') => (&digits[..digits.len() - 1], 1 << 10),
+            Some('M') => (&digits[..digits.len() - 1], 1 << 20),
+            Some('G') => (&digits[..digits.len() - 1], 1 << 30),
+            _ => (digits, 1),
+        };
+        match digits.parse::<usize>().ok().and_then(|n| n.checked_mul(scale)) {
+            Some(bytes) if bytes > 0 => Ok(bytes),
+            _ => Err(format!("Invalid memory size '{}'. Expected a positive number of bytes, optionally followed by K, M or G", size)),
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct SortConfig {
+        pub order: ChromOrder,
+        //approximate number of bytes of input to hold in memory before
+        //sorting it and writing it to a temporary file
+        pub max_mem: usize,
+        //where to write the temporary files
+        pub tmp_dir: PathBuf,
+        //the most temporary files to merge at once; more are merged in
+        //several passes, to stay under the limit on open files
+        pub fan_in: usize,
+        //for the intervals outside the genome, if the order is a genome's
+        pub bounds: OutOfBounds,
+        //renames the chromosome column of every line (see chrom_alias)
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl SortConfig {
+        pub fn new(order: ChromOrder) -> SortConfig {
+            SortConfig{order, max_mem: 1 << 30, tmp_dir: std::env::temp_dir(), fan_in: 64, bounds: OutOfBounds::Reject, renamer: None}
+        }
+    }
+
+    //reads a file and checks that it is sorted by chromosome (in the
+    //given order), then start
+    pub(crate) struct SortedReader<'a> {
+        bg: BgIterator,
+        fname: &'a str,
+        order: &'a ChromOrder,
+        last: Option<ChromSeg>,
+        seen: HashSet<String>,
+    }
+
+    impl<'a> SortedReader<'a> {
+        pub(crate) fn new(bg: BgIterator, fname: &'a str, order: &'a ChromOrder) -> SortedReader<'a> {
+            SortedReader{bg, fname, order, last: None, seen: HashSet::new()}
+        }
+
+        //open a file for a sweep, checking its intervals against the genome
+        //when the order is that of a genome
+        pub(crate) fn open(fname: &'a str, order: &'a ChromOrder, bounds: OutOfBounds, renamer: Option<&Rc<Renamer>>) -> Result<SortedReader<'a>, String> {
+            let mut bg = BgIterator::open(fname, renamer)?;
+            if let ChromOrder::Genome(genome) = order {
+                bg = bg.with_bounds(BoundsCheck::new(genome.clone(), bounds));
+            }
+            Ok(SortedReader::new(bg, fname, order))
+        }
+
+        pub(crate) fn order(&self) -> &'a ChromOrder {
+            self.order
+        }
+
+        pub(crate) fn try_next(&mut self) -> Result<Option<BgLine>, String> {
+            let line = match self.bg.try_next()? {
+                None => return Ok(None),
+                Some(line) => line,
+            };
+            self.order.rank(&line.coords.chrom).map_err(|msg| format!("Error in '{}', line {}: {}", self.fname, self.bg.lineno(), msg))?;
+            let out_of_order = match self.last {
+                Some(ref last) if last.chrom == line.coords.chrom => line.coords.start < last.start,
+                Some(ref last) if self.order.compare_chroms(&line.coords.chrom, &last.chrom) == Ordering::Less => true,
+                _ => !self.seen.insert(line.coords.chrom.clone()),
+            };
+            if out_of_order {
+                return Err(format!("Error: Sorted input specified, but the file {} has the following out of order record\n{}", self.fname, line));
+            }
+            self.last = Some(line.coords.clone());
+            Ok(Some(line))
+        }
+    }
+
+    //a line of a BED file, with the fields needed to sort it
+    #[derive(Debug)]
+    struct SortRecord {
+        line: String,
+        chrom_start: usize,
+        chrom_stop: usize,
+        rank: usize,
+        start: Coord,
+        stop: Coord,
+    }
+
+    impl SortRecord {
+        //None if the bounds check drops the line (a clipped stop, or a
+        //renamed chromosome, is rewritten in the line)
+        fn new(mut line: String, order: &ChromOrder, bounds: Option<&mut BoundsCheck>, renamer: Option<&Renamer>) -> Result<Option<SortRecord>, String> {
+            let (chrom, start, stop, chrom_col, stop_col) = {
+                let cols: Vec<&str> = line.split_whitespace().take(3).collect();
+                if cols.len() < 3 {
+                    return Err(format!("expected at least 3 fields, received {}", cols.len()));
+                }
+                let start: Coord = cols[1].parse().map_err(|_| format!("expected unsigned integer, received '{}'", cols[1]))?;
+                let stop:  Coord = cols[2].parse().map_err(|_| format!("expected unsigned integer, received '{}'", cols[2]))?;
+                if start > stop {
+                    return Err(format!("start ({}) is greater than stop ({})", start, stop));
+                }
+                //the byte ranges of the chrom and stop columns
+                let chrom_start = cols[0].as_ptr() as usize - line.as_ptr() as usize;
+                let stop_start = cols[2].as_ptr() as usize - line.as_ptr() as usize;
+                (cols[0].to_string(), start, stop, chrom_start..chrom_start + cols[0].len(), stop_start..stop_start + cols[2].len())
+            };
+            let chrom = match renamer {
+                Some(renamer) => renamer.rename(&chrom).into_owned(),
+                None => chrom,
+            };
+            let mut seg = ChromSeg{chrom, start, stop};
+            if let Some(bounds) = bounds {
+                if !bounds.check(&mut seg)? {
+                    return Ok(None);
+                }
+            }
+            //the stop column comes after the chrom, so it is rewritten first
+            if seg.stop != stop {
+                line.replace_range(stop_col, &seg.stop.to_string());
+            }
+            if line[chrom_col.clone()] != seg.chrom {
+                line.replace_range(chrom_col, &seg.chrom);
+            }
+            let rank = order.rank(&seg.chrom)?;
+            let chrom_start = line.len() - line.trim_start().len();
+            let chrom_stop = chrom_start + seg.chrom.len();
+            Ok(Some(SortRecord{line, chrom_start, chrom_stop, rank, start, stop: seg.stop}))
+        }
+
+        fn chrom(&self) -> &str {
+            &self.line[self.chrom_start..self.chrom_stop]
+        }
+
+        //approximate memory used by the record
+        fn size(&self) -> usize {
+            self.line.capacity() + std::mem::size_of::<SortRecord>()
+        }
+    }
+
+    //header lines are written before the sorted lines, in their original order
+    fn is_header(line: &str) -> bool {
+        line.starts_with('#') || line.starts_with("track") || line.starts_with("browser")
+    }
+
+    //used to give every temporary file a distinct name
+    static NEXT_CHUNK: AtomicUsize = AtomicUsize::new(0);
+
+    //sorted runs of the input written to temporary files, which are
+    //removed when this is dropped
+    struct Chunks {
+        paths: Vec<PathBuf>,
+    }
+
+    impl Chunks {
+        //create a new temporary file
+        fn create(&mut self, tmp_dir: &Path) -> Result<(PathBuf, BufWriter<File>), String> {
+            let path = tmp_dir.join(format!("ironbed-sort.{}.{}.tmp", std::process::id(), NEXT_CHUNK.fetch_add(1, AtomicOrdering::SeqCst)));
+            let handle = File::create(&path).map_err(|err| format!("Error with '{}': {}", path.display(), err))?;
+            self.paths.push(path.clone());
+            Ok((path, BufWriter::new(handle)))
+        }
+
+        fn write(&mut self, records: &[SortRecord], tmp_dir: &Path) -> Result<PathBuf, String> {
+            let (path, mut output) = self.create(tmp_dir)?;
+            for record in records {
+                writeln!(output, "{}", record.line).map_err(|err| format!("Error with '{}': {}", path.display(), err))?;
+            }
+            output.flush().map_err(|err| format!("Error with '{}': {}", path.display(), err))?;
+            Ok(path)
+        }
+    }
+
+    impl Drop for Chunks {
+        fn drop(&mut self) {
+            for path in &self.paths {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    //the next line of a temporary file, ordered so that the BinaryHeap
+    //(a max-heap) pops the smallest line, and the earliest chunk on ties
+    struct ChunkHead<'a> {
+        record: SortRecord,
+        chunk: usize,
+        order: &'a ChromOrder,
+    }
+
+    impl<'a> Ord for ChunkHead<'a> {
+        fn cmp(&self, other: &Self) -> Ordering {
+            compare_records(self.order, &other.record, &self.record).then(other.chunk.cmp(&self.chunk))
+        }
+    }
+
+    impl<'a> PartialOrd for ChunkHead<'a> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl<'a> PartialEq for ChunkHead<'a> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl<'a> Eq for ChunkHead<'a> {}
+
+    fn read_record<R: BufRead>(reader: &mut R, path: &Path, order: &ChromOrder) -> Result<Option<SortRecord>, String> {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Err(err) => Err(format!("Error with '{}': {}", path.display(), err)),
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                line.truncate(line.trim_end_matches(&['\n', '\r'][..]).len());
+                SortRecord::new(line, order, None, None).map_err(|msg| format!("Error in '{}': {}", path.display(), msg))
+            }
+        }
+    }
+
+    //merge sorted temporary files, passing each line to emit
+    //the files are removed once they are open
+    fn merge_chunks<F: FnMut(&str) -> Result<(), String>>(paths: &[PathBuf], order: &ChromOrder, mut emit: F) -> Result<(), String> {
+        let mut readers = Vec::with_capacity(paths.len());
+        let mut heap = BinaryHeap::with_capacity(paths.len());
+        for (chunk, path) in paths.iter().enumerate() {
+            let handle = File::open(path).map_err(|err| format!("Error with '{}': {}", path.display(), err))?;
+            let mut reader = BufReader::new(handle);
+            if let Some(record) = read_record(&mut reader, path, order)? {
+                heap.push(ChunkHead{record, chunk, order});
+            }
+            readers.push(reader);
+        }
+        //the open files can still be read after they are removed (on Unix),
+        //so nothing is left behind if a broken pipe exits the process early
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+        while let Some(head) = heap.pop() {
+            emit(&head.record.line)?;
+            if let Some(record) = read_record(&mut readers[head.chunk], &paths[head.chunk], order)? {
+                heap.push(ChunkHead{record, chunk: head.chunk, order});
+            }
+        }
+        Ok(())
+    }
+
+    //sort BED or bedGraph files ('-' for stdin) and write them to output
+    //the sort is stable: lines with the same chromosome, start and stop
+    //stay in the order they were read
+    //inputs larger than config.max_mem are sorted in chunks, which are
+    //written to temporary files and then merged, config.fan_in at a time
+    pub fn sort_bed<W: Write>(filenames: &[&str], config: &SortConfig, output: &mut W) -> Result<(), String> {
+        let mut headers: Vec<String> = Vec::new();
+        let mut records: Vec<SortRecord> = Vec::new();
+        let mut used = 0;
+        let mut chunks = Chunks{paths: Vec::new()};
+        //the temporary files that are still to be merged, in input order
+        let mut pending: Vec<PathBuf> = Vec::new();
+        for fname in filenames {
+            let mut reader: Box<dyn BufRead> = if *fname == "-" {
+                Box::new(BufReader::new(std::io::stdin()))
+            } else {
+                chrom_sizes::open_maybe_gz(fname)?
+            };
+            //with a genome order, the intervals are checked against the genome
+            let mut bounds = match config.order {
+                ChromOrder::Genome(ref genome) => Some(BoundsCheck::new(genome.clone(), config.bounds)),
+                _ => None,
+            };
+            let mut lineno = 0;
+            loop {
+                let mut line = String::new();
+                let bytes = reader.read_line(&mut line).map_err(|err| format!("Error with '{}': {}", fname, err))?;
+                if bytes == 0 {
+                    break;
+                }
+                lineno += 1;
+                line.truncate(line.trim_end_matches(&['\n', '\r'][..]).len());
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if is_header(&line) {
+                    headers.push(line);
+                    continue;
+                }
+                let record = SortRecord::new(line, &config.order, bounds.as_mut(), config.renamer.as_deref()).map_err(|msg| format!("Error in '{}', line {}: {}", fname, lineno, msg))?;
+                let record = match record {
+                    Some(record) => record,
+                    None => continue,
+                };
+                used += record.size();
+                records.push(record);
+                if used > config.max_mem {
+                    records.sort_by(|a, b| compare_records(&config.order, a, b));
+                    pending.push(chunks.write(&records, &config.tmp_dir)?);
+                    records.clear();
+                    used = 0;
+                }
+            }
+            if let Some(bounds) = bounds {
+                bounds.report(fname);
+            }
+        }
+        for header in &headers {
+            write_line(output, header);
+        }
+        records.sort_by(|a, b| compare_records(&config.order, a, b));
+        if pending.is_empty() {
+            for record in &records {
+                write_line(output, &record.line);
+            }
+            return Ok(());
+        }
+        if !records.is_empty() {
+            pending.push(chunks.write(&records, &config.tmp_dir)?);
+            records.clear();
+        }
+        //merge runs of neighbouring chunks until few enough are left (which
+        //keeps the sort stable), then merge those into the output
+        let fan_in = config.fan_in.max(2);
+        while pending.len() > fan_in {
+            let mut merged = Vec::with_capacity(pending.len() / fan_in + 1);
+            for group in pending.chunks(fan_in) {
+                if group.len() == 1 {
+                    merged.push(group[0].clone());
+                    continue;
+                }
+                let (path, mut handle) = chunks.create(&config.tmp_dir)?;
+                let context = |err: std::io::Error| format!("Error with '{}': {}", path.display(), err);
+                merge_chunks(group, &config.order, |line| writeln!(handle, "{}", line).map_err(context))?;
+                handle.flush().map_err(context)?;
+                merged.push(path);
+            }
+            pending = merged;
+        }
+        merge_chunks(&pending, &config.order, |line| {
+            write_line(output, &line);
+            Ok(())
+        })
+    }
+
+    pub fn sort_main(filenames: Vec<&str>, config: SortConfig) -> Result<(), String> {
+        run_to_stdout(|output| sort_bed(&filenames, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_sort {
+        use super::*;
+        use super::super::chrom_alias::AliasTable;
+        use super::super::chrom_sizes::{Genome, SizesMode};
+        use super::super::output::output_lines;
+
+        fn sorted(filenames: &[&str], config: &SortConfig) -> Result<Vec<String>, String> {
+            output_lines(|output| sort_bed(filenames, config, output))
+        }
+
+        #[test]
+        fn sort_lexicographic() {
+            let lines = sorted(&["test/sort/unsorted.bed"], &SortConfig::new(ChromOrder::Lexicographic)).unwrap();
+            assert_eq!(lines, vec!["track name=unsorted",
+                                   "chr1\t100\t200\tb",
+                                   "chr1\t200\t300\tfirst",
+                                   "chr1\t200\t300\tsecond",
+                                   "chr1\t200\t300\tthird",
+                                   "chr10\t100\t200\td",
+                                   "chr10\t500\t600\tc",
+                                   "chr2\t300\t350\te",
+                                   "chr2\t300\t400\ta",
+                                   "chr9\t1\t2\tf",
+                                   "chrX\t10\t20\tx"]);
+        }
+
+        #[test]
+        fn sort_natural() {
+            let lines = sorted(&["test/sort/unsorted.bed"], &SortConfig::new(ChromOrder::Natural)).unwrap();
+            let chroms: Vec<&str> = lines[1..].iter().map(|line| line.split('\t').next().unwrap()).collect();
+            assert_eq!(chroms, vec!["chr1", "chr1", "chr1", "chr1", "chr2", "chr2", "chr9", "chr10", "chr10", "chrX"]);
+        }
+
+        #[test]
+        fn sort_genome() {
+            let genome = Genome::from_file("test/sort/genome.chrom.sizes").unwrap();
+            let config = SortConfig::new(ChromOrder::Genome(Rc::new(genome)));
+            let lines = sorted(&["test/sort/unsorted.bed"], &config).unwrap();
+            let chroms: Vec<&str> = lines[1..].iter().map(|line| line.split('\t').next().unwrap()).collect();
+            assert_eq!(chroms, vec!["chrX", "chr2", "chr2", "chr10", "chr10", "chr9", "chr1", "chr1", "chr1", "chr1"]);
+            let genome = Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap();
+            let config = SortConfig::new(ChromOrder::Genome(Rc::new(genome)));
+            let expect = String::from("Error in 'test/sort/unsorted.bed', line 2: unknown chromosome 'chr10'");
+            if let Err(msg) = sorted(&["test/sort/unsorted.bed"], &config) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from sort_bed(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn sort_bounds() {
+            let genome = Genome::load("test/fasta/small.fa.fai", false, SizesMode::Strict).unwrap();
+            let mut config = SortConfig::new(ChromOrder::Genome(Rc::new(genome)));
+            let expect = String::from("Error in 'test/bedgraph/out_of_bounds.bg', line 2: interval chrA:20-30 extends past the end of the chromosome (23)");
+            if let Err(msg) = sorted(&["test/bedgraph/out_of_bounds.bg"], &config) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from sort_bed(), received Ok(_) instead");
+            }
+            config.bounds = OutOfBounds::Clip;
+            assert_eq!(sorted(&["test/bedgraph/out_of_bounds.bg"], &config).unwrap(), vec!["chrA\t0\t10\t1", "chrA\t20\t23\t2", "chrB\t10\t15\t4"]);
+            let expect = String::from("Error in 'test/bedgraph/reversed.bg', line 2: start (100) is greater than stop (50)");
+            if let Err(msg) = sorted(&["test/bedgraph/reversed.bg"], &SortConfig::new(ChromOrder::Lexicographic)) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from sort_bed(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn sort_renamed() {
+            //the chromosome column is rewritten, and the rest of the line kept
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let mut config = SortConfig::new(ChromOrder::Natural);
+            config.renamer = Some(Rc::new(Renamer::new(table, "ucsc").unwrap()));
+            assert_eq!(sorted(&["test/alias/1.ensembl.bg"], &config).unwrap(),
+                       sorted(&["test/unionbedg/1.bg"], &SortConfig::new(ChromOrder::Natural)).unwrap());
+        }
+
+        #[test]
+        fn sorted_reader_unknown() {
+            //chromosomes missing from the genome have no place in its order
+            let genome = Genome::from_file("test/chrom.sizes/tair10.chrom.sizes").unwrap();
+            let order = ChromOrder::Genome(Rc::new(genome));
+            let mut reader = SortedReader::new(BgIterator::open("test/closest/a.bed", None).unwrap(), "test/closest/a.bed", &order);
+            let expect = String::from("Error in 'test/closest/a.bed', line 1: unknown chromosome 'chr1'");
+            if let Err(msg) = reader.try_next() {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from try_next(), received Ok(_) instead");
+            }
+            assert_eq!(order.compare_chroms("chrY", "chrZ"), Ordering::Less);
+            assert_eq!(order.compare_chroms("chrZ", "Chr5"), Ordering::Greater);
+        }
+
+        #[test]
+        fn sort_spill() {
+            //a tiny memory limit writes every line to its own temporary file
+            let mut config = SortConfig::new(ChromOrder::Natural);
+            let expected = sorted(&["test/sort/unsorted.bed", "test/unionbedg/long.bg"], &config).unwrap();
+            config.max_mem = 1;
+            let spilled = sorted(&["test/sort/unsorted.bed", "test/unionbedg/long.bg"], &config).unwrap();
+            assert_eq!(spilled, expected);
+            config.max_mem = 300;
+            let spilled = sorted(&["test/sort/unsorted.bed", "test/unionbedg/long.bg"], &config).unwrap();
+            assert_eq!(spilled, expected);
+        }
+
+        #[test]
+        fn sort_merge_passes() {
+            //more temporary files than can be merged at once
+            let mut config = SortConfig::new(ChromOrder::Lexicographic);
+            let expected = sorted(&["test/sort/unsorted.bed", "test/unionbedg/long.bg"], &config).unwrap();
+            config.max_mem = 1;
+            config.tmp_dir = std::env::temp_dir().join(format!("ironbed-test-sort-{}", std::process::id()));
+            fs::create_dir_all(&config.tmp_dir).unwrap();
+            for fan_in in &[2, 3, 5] {
+                config.fan_in = *fan_in;
+                let spilled = sorted(&["test/sort/unsorted.bed", "test/unionbedg/long.bg"], &config).unwrap();
+                assert_eq!(spilled, expected);
+            }
+            //the merged files are removed too
+            let left = fs::read_dir(&config.tmp_dir).unwrap().count();
+            fs::remove_dir_all(&config.tmp_dir).unwrap();
+            assert_eq!(left, 0);
+        }
+
+        #[test]
+        fn sort_bad_line() {
+            let expect = String::from("Error in 'test/sort/bad.bed', line 2: expected unsigned integer, received 'abc'");
+            if let Err(msg) = sorted(&["test/sort/bad.bed"], &SortConfig::new(ChromOrder::Lexicographic)) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from sort_bed(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn mem_sizes() {
+            assert_eq!(parse_mem_size("1000"), Ok(1000));
+            assert_eq!(parse_mem_size("4K"), Ok(4096));
+            assert_eq!(parse_mem_size("500M"), Ok(500 << 20));
+            assert_eq!(parse_mem_size("2gb"), Ok(2 << 30));
+            assert!(parse_mem_size("lots").is_err());
+            assert!(parse_mem_size("0").is_err());
+        }
+    }
+}
+
+pub mod merge {
+    use std::collections::HashSet;
+    use std::io::Write;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::BgIterator;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use std::rc::Rc;
+
+    //an operation applied to a column of the merged lines (-o)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MergeOp {
+        Sum,
+        Mean,
+        Count,
+        Collapse,
+        Distinct,
+        Min,
+        Max,
+    }
+
+    impl MergeOp {
+        pub fn parse(op: &str) -> Result<MergeOp, String> {
+            match op {
+                "sum" => Ok(MergeOp::Sum),
+                "mean" => Ok(MergeOp::Mean),
+                "count" => Ok(MergeOp::Count),
+                "collapse" => Ok(MergeOp::Collapse),
+                "distinct" => Ok(MergeOp::Distinct),
+                "min" => Ok(MergeOp::Min),
+                "max" => Ok(MergeOp::Max),
+                _ => Err(format!("***** ERROR: Invalid operation \"{}\". Expected one of sum, mean, count, collapse, distinct, min, max", op)),
+            }
+        }
+
+        fn is_numeric(self) -> bool {
+            matches!(self, MergeOp::Sum | MergeOp::Mean | MergeOp::Min | MergeOp::Max)
+        }
+
+        //apply the operation to the values of a column, which are all
+        //numeric for the numeric operations ('.' if there are none)
+        fn apply(self, values: &[String]) -> String {
+            let numbers = || values.iter().map(|value| value.parse::<f64>().unwrap());
+            match self {
+                MergeOp::Count => return values.len().to_string(),
+                MergeOp::Collapse => return values.join(","),
+                MergeOp::Distinct => {
+                    let mut seen = HashSet::new();
+                    let distinct: Vec<&str> = values.iter().map(|value| value.as_str()).filter(|value| seen.insert(*value)).collect();
+                    return distinct.join(",");
+                },
+                _ if values.is_empty() => return ".".to_string(),
+                _ => (),
+            }
+            let result = match self {
+                MergeOp::Sum => numbers().sum(),
+                MergeOp::Mean => numbers().sum::<f64>() / values.len() as f64,
+                MergeOp::Min => numbers().fold(f64::INFINITY, f64::min),
+                MergeOp::Max => numbers().fold(f64::NEG_INFINITY, f64::max),
+                _ => unreachable!(),
+            };
+            result.to_string()
+        }
+    }
+
+    //pair up the columns (-c) and operations (-o), both comma-separated
+    //as in bedtools, a single column or operation is used for every pair
+    pub fn parse_columns(columns: &str, ops: &str) -> Result<Vec<(usize, MergeOp)>, String> {
+        pair_columns(columns, ops, MergeOp::parse)
+    }
+
+    pub(crate) fn pair_columns<T: Copy>(columns: &str, ops: &str, parse: fn(&str) -> Result<T, String>) -> Result<Vec<(usize, T)>, String> {
+        let columns = columns.split(',').map(|col| match col.trim().parse::<usize>() {
+            Ok(col) if col > 0 => Ok(col),
+            _ => Err(format!("***** ERROR: Invalid column \"{}\". Columns are numbered from 1", col)),
+        }).collect::<Result<Vec<usize>, String>>()?;
+        let ops = ops.split(',').map(|op| parse(op.trim())).collect::<Result<Vec<T>, String>>()?;
+        match (columns.len(), ops.len()) {
+            (_, 1) => Ok(columns.into_iter().map(|col| (col, ops[0])).collect()),
+            (1, _) => Ok(ops.into_iter().map(|op| (columns[0], op)).collect()),
+            (n, m) if n == m => Ok(columns.into_iter().zip(ops).collect()),
+            (n, m) => Err(format!("***** ERROR: There are {} columns given, but there are {} operations.", n, m)),
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct MergeConfig {
+        //the largest gap between intervals that are merged; 0 merges
+        //book-ended intervals, and negative values require an overlap
+        pub distance: i64,
+        //only merge intervals on the same strand (column 6)
+        pub strand: bool,
+        pub columns: Vec<(usize, MergeOp)>,
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    //a merged interval and the values of the requested columns
+    struct Cluster {
+        strand: String,
+        seg: ChromSeg,
+        values: Vec<Vec<String>>,
+    }
+
+    impl Cluster {
+        fn write<W: Write>(&self, output: &mut W, config: &MergeConfig) {
+            let mut line = self.seg.to_string();
+            for (values, (_, op)) in self.values.iter().zip(&config.columns) {
+                line.push('\t');
+                line.push_str(&op.apply(values));
+            }
+            write_line(output, &line);
+        }
+    }
+
+    //the open clusters (one per strand) and the closed ones that cannot
+    //be written yet, because an open cluster starts before them
+    struct Clusters {
+        open: Vec<Cluster>,
+        closed: Vec<Cluster>,
+    }
+
+    impl Clusters {
+        fn close(&mut self, index: usize) {
+            let cluster = self.open.swap_remove(index);
+            self.closed.push(cluster);
+        }
+
+        //write the closed clusters that start before every open one
+        fn flush<W: Write>(&mut self, output: &mut W, config: &MergeConfig) {
+            let first_open = self.open.iter().map(|cluster| cluster.seg.start).min().unwrap_or(Coord::MAX);
+            self.closed.sort_by_key(|cluster| cluster.seg.start);
+            let ready = self.closed.iter().take_while(|cluster| cluster.seg.start <= first_open).count();
+            for cluster in self.closed.drain(..ready) {
+                cluster.write(output, config);
+            }
+        }
+    }
+
+    //merge the overlapping (or nearby) intervals of a file that is sorted
+    //by chromosome, then start
+    pub fn merge_bed<W: Write>(filename: &str, config: &MergeConfig, output: &mut W) -> Result<(), String> {
+        let mut bg = BgIterator::open(filename, config.renamer.as_ref())?;
+        let mut clusters = Clusters{open: Vec::new(), closed: Vec::new()};
+        let mut seen_chroms: HashSet<String> = HashSet::new();
+        let mut last: Option<ChromSeg> = None;
+        while let Some(line) = bg.try_next()? {
+            let fields: Vec<&str> = line.data.as_ref().map_or(Vec::new(), |data| data.split('\t').collect());
+            //check the order, and close every cluster at the end of a chromosome
+            let out_of_order = match last {
+                Some(ref last) if last.chrom == line.coords.chrom => line.coords.start < last.start,
+                _ => !seen_chroms.insert(line.coords.chrom.clone()),
+            };
+            if out_of_order {
+                return Err(format!("Error: Sorted input specified, but the file {} has the following out of order record\n{}", filename, line));
+            }
+            if last.as_ref().is_some_and(|last| last.chrom != line.coords.chrom) {
+                while !clusters.open.is_empty() {
+                    clusters.close(0);
+                }
+                clusters.flush(output, config);
+            }
+            last = Some(line.coords.clone());
+            //collect the values of the requested columns
+            let mut values = Vec::with_capacity(config.columns.len());
+            for (col, op) in &config.columns {
+                let value = match col {
+                    1 => line.coords.chrom.clone(),
+                    2 => line.coords.start.to_string(),
+                    3 => line.coords.stop.to_string(),
+                    _ => match fields.get(col - 4) {
+                        Some(value) => value.to_string(),
+                        None => return Err(format!("***** ERROR: Requested column {}, but database file {} only has fields 1 - {}.", col, filename, fields.len() + 3)),
+                    },
+                };
+                //'nan' and 'inf' parse, but would make every result the same
+                let finite = match value.parse::<f64>() {
+                    Ok(number) => number.is_finite(),
+                    Err(_) => false,
+                };
+                if op.is_numeric() && !finite {
+                    eprintln!("***** WARNING: Non numeric value {} in {}.", value, col);
+                    values.push(None);
+                } else {
+                    values.push(Some(value));
+                }
+            }
+            let strand = if config.strand { line.strand().unwrap_or(".").to_string() } else { String::new() };
+            let index = clusters.open.iter().position(|cluster| cluster.strand == strand);
+            if let Some(index) = index {
+                let cluster = &mut clusters.open[index];
+                if line.coords.start as i64 - cluster.seg.stop as i64 <= config.distance {
+                    cluster.seg.stop = cluster.seg.stop.max(line.coords.stop);
+                    for (column, value) in cluster.values.iter_mut().zip(values) {
+                        column.extend(value);
+                    }
+                    continue;
+                }
+                clusters.close(index);
+            }
+            let values = values.into_iter().map(|value| value.into_iter().collect()).collect();
+            clusters.open.push(Cluster{strand, seg: line.coords, values});
+            clusters.flush(output, config);
+        }
+        while !clusters.open.is_empty() {
+            clusters.close(0);
+        }
+        clusters.flush(output, config);
+        Ok(())
+    }
+
+    pub fn merge_main(filename: &str, config: MergeConfig) -> Result<(), String> {
+        run_to_stdout(|output| merge_bed(filename, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_merge {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn merged(filename: &str, config: &MergeConfig) -> Result<Vec<String>, String> {
+            output_lines(|output| merge_bed(filename, config, output))
+        }
+
+        #[test]
+        fn merge_defaults() {
+            let lines = merged("test/merge/input.bed", &MergeConfig::default()).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t300", "chr1\t400\t500", "chr1\t505\t600", "chr2\t10\t30"]);
+        }
+
+        #[test]
+        fn merge_distance() {
+            let config = MergeConfig{distance: 5, ..MergeConfig::default()};
+            let lines = merged("test/merge/input.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t300", "chr1\t400\t600", "chr2\t10\t30"]);
+            //negative distances require an overlap
+            let config = MergeConfig{distance: -10, ..MergeConfig::default()};
+            let lines = merged("test/merge/input.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t250", "chr1\t250\t300", "chr1\t400\t500", "chr1\t505\t600", "chr2\t10\t30"]);
+        }
+
+        #[test]
+        fn merge_strand() {
+            let config = MergeConfig{strand: true, columns: parse_columns("6", "distinct").unwrap(), ..MergeConfig::default()};
+            let lines = merged("test/merge/input.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t200\t+",
+                                   "chr1\t150\t250\t-",
+                                   "chr1\t250\t300\t+",
+                                   "chr1\t400\t500\t+",
+                                   "chr1\t505\t600\t-",
+                                   "chr2\t10\t30\t+"]);
+        }
+
+        #[test]
+        fn merge_operations() {
+            let config = MergeConfig{columns: parse_columns("4,5,5,5,5,4,1", "collapse,sum,mean,min,max,distinct,count").unwrap(), ..MergeConfig::default()};
+            let lines = merged("test/merge/input.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t300\ta,b,c\t6\t2\t1\t3\ta,b,c\t3",
+                                   "chr1\t400\t500\td\t4\t4\t4\t4\td\t1",
+                                   "chr1\t505\t600\te\t5\t5\t5\t5\te\t1",
+                                   "chr2\t10\t30\tf,a\t13\t6.5\t6\t7\tf,a\t2"]);
+            //non-numeric values are skipped by the numeric operations
+            let config = MergeConfig{columns: parse_columns("4", "sum,count").unwrap(), ..MergeConfig::default()};
+            let lines = merged("test/merge/text.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t300\t2.5\t2"]);
+            let config = MergeConfig{columns: parse_columns("5", "sum,mean,max,count").unwrap(), ..MergeConfig::default()};
+            let lines = merged("test/merge/nonfinite.bed", &config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t320\t5\t5\t5\t3"]);
+        }
+
+        #[test]
+        fn merge_parse_columns() {
+            assert_eq!(parse_columns("5", "sum,max"), Ok(vec![(5, MergeOp::Sum), (5, MergeOp::Max)]));
+            assert_eq!(parse_columns("4,5", "count"), Ok(vec![(4, MergeOp::Count), (5, MergeOp::Count)]));
+            assert_eq!(parse_columns("4,5,6", "count,sum"), Err(String::from("***** ERROR: There are 3 columns given, but there are 2 operations.")));
+            assert!(parse_columns("4", "median").is_err());
+            assert!(parse_columns("0", "sum").is_err());
+        }
+
+        #[test]
+        fn merge_errors() {
+            let expect = String::from("Error: Sorted input specified, but the file test/merge/unsorted.bed has the following out of order record\nchr1\t300\t400");
+            if let Err(msg) = merged("test/merge/unsorted.bed", &MergeConfig::default()) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from merge_bed(), received Ok(_) instead");
+            }
+            let config = MergeConfig{columns: parse_columns("7", "sum").unwrap(), ..MergeConfig::default()};
+            let expect = String::from("***** ERROR: Requested column 7, but database file test/merge/input.bed only has fields 1 - 6.");
+            if let Err(msg) = merged("test/merge/input.bed", &config) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from merge_bed(), received Ok(_) instead");
+            }
+        }
+    }
+}
+
+pub mod intersect {
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use super::chrom_sizes::{ChromOrder, OutOfBounds};
+    use super::sort::SortedReader;
+    use std::rc::Rc;
+
+    //what to report for each interval in A
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Report {
+        //a line for every overlap (the default, and -wa, -wb, -wo)
+        Overlaps,
+        //A once if it has any overlap (-u)
+        Unique,
+        //A if it has no overlap (-v)
+        Missing,
+        //A and its number of overlaps (-c)
+        Count,
+    }
+
+    //which strands can overlap (strands are in column 6)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Strandedness {
+        Any,
+        //-s
+        Same,
+        //-S
+        Opposite,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct IntersectConfig {
+        pub report: Report,
+        //-wa: write the original A interval instead of the overlapping part
+        pub write_a: bool,
+        //-wb: also write the B interval
+        pub write_b: bool,
+        //-wo: write A, B and the number of overlapping bases
+        pub write_overlap: bool,
+        //minimum overlap as a fraction of A (-f) and of B (-F)
+        pub min_frac_a: f64,
+        pub min_frac_b: f64,
+        //-r: min_frac_a must also hold for B
+        pub reciprocal: bool,
+        pub strand: Strandedness,
+        //written before each B interval, when there are several B files
+        pub labels: Vec<String>,
+        //Some for a sweep over inputs sorted in this order, None to read
+        //B into memory
+        pub sorted: Option<ChromOrder>,
+        //for the intervals outside a genome given for the order
+        pub bounds: OutOfBounds,
+        //renames the chromosomes of A and of every B file
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for IntersectConfig {
+        fn default() -> IntersectConfig {
+            IntersectConfig{
+                report: Report::Overlaps,
+                write_a: false,
+                write_b: false,
+                write_overlap: false,
+                min_frac_a: 0.0,
+                min_frac_b: 0.0,
+                reciprocal: false,
+                strand: Strandedness::Any,
+                labels: Vec::new(),
+                sorted: None,
+                bounds: OutOfBounds::Reject,
+                renamer: None,
+            }
+        }
+    }
+
+    fn overlap(a: &ChromSeg, b: &ChromSeg) -> Coord {
+        a.stop.min(b.stop).saturating_sub(a.start.max(b.start))
+    }
+
+    //does b overlap a enough, on the right strand?
+    fn accepts(a: &BgLine, b: &BgLine, config: &IntersectConfig) -> bool {
+        let bases = overlap(&a.coords, &b.coords);
+        if bases == 0 {
+            return false;
+        }
+        let frac_a = bases as f64 / (a.coords.stop - a.coords.start) as f64;
+        let frac_b = bases as f64 / (b.coords.stop - b.coords.start) as f64;
+        let min_frac_b = if config.reciprocal { config.min_frac_a.max(config.min_frac_b) } else { config.min_frac_b };
+        if frac_a < config.min_frac_a || frac_b < min_frac_b {
+            return false;
+        }
+        match config.strand {
+            Strandedness::Any => true,
+            Strandedness::Same => a.strand().unwrap_or(".") == b.strand().unwrap_or("."),
+            Strandedness::Opposite => matches!((a.strand(), b.strand()), (Some("+"), Some("-")) | (Some("-"), Some("+"))),
+        }
+    }
+
+    //write the lines for an interval in A and the intervals it overlaps
+    //(as pairs of the B file's index and interval)
+    fn report<W: Write>(output: &mut W, a: &BgLine, hits: &[(usize, &BgLine)], config: &IntersectConfig) {
+        match config.report {
+            Report::Missing => if hits.is_empty() { write_line(output, a) },
+            Report::Unique => if !hits.is_empty() { write_line(output, a) },
+            Report::Count => write_line(output, &format!("{}\t{}", a, hits.len())),
+            Report::Overlaps => {
+                for (file, b) in hits {
+                    let mut line = if config.write_a || config.write_overlap {
+                        a.to_string()
+                    } else {
+                        let coords = ChromSeg{chrom: a.coords.chrom.clone(),
+                                              start: a.coords.start.max(b.coords.start),
+                                              stop: a.coords.stop.min(b.coords.stop)};
+                        BgLine{coords, data: a.data.clone()}.to_string()
+                    };
+                    if config.write_b || config.write_overlap {
+                        if let Some(label) = config.labels.get(*file) {
+                            line.push('\t');
+                            line.push_str(label);
+                        }
+                        line.push('\t');
+                        line.push_str(&b.to_string());
+                    }
+                    if config.write_overlap {
+                        line.push('\t');
+                        line.push_str(&overlap(&a.coords, &b.coords).to_string());
+                    }
+                    write_line(output, &line);
+                }
+            },
+        }
+    }
+
+    //the intervals of a sorted B file that may overlap the current A interval
+    pub(crate) struct Window<'a> {
+        reader: SortedReader<'a>,
+        next: Option<BgLine>,
+        pub(crate) lines: Vec<BgLine>,
+    }
+
+    impl<'a> Window<'a> {
+        pub(crate) fn new(reader: SortedReader<'a>) -> Result<Window<'a>, String> {
+            let mut reader = reader;
+            let next = reader.try_next()?;
+            Ok(Window{reader, next, lines: Vec::new()})
+        }
+
+        //move the window to an interval in A (which must not start before
+        //the previous one)
+        pub(crate) fn advance(&mut self, a: &ChromSeg) -> Result<(), String> {
+            self.lines.retain(|b| b.coords.chrom == a.chrom && b.coords.stop > a.start);
+            while let Some(b) = self.next.take() {
+                match self.reader.order().compare_chroms(&b.coords.chrom, &a.chrom) {
+                    Ordering::Less => (),
+                    Ordering::Equal if b.coords.start < a.stop => {
+                        if b.coords.stop > a.start {
+                            self.lines.push(b);
+                        }
+                    },
+                    _ => {
+                        self.next = Some(b);
+                        break;
+                    },
+                }
+                self.next = self.reader.try_next()?;
+            }
+            Ok(())
+        }
+    }
+
+    //the intervals of an unsorted B file on one chromosome, sorted by start,
+    //with the largest stop of each prefix for finding the overlaps quickly
+    #[derive(Default)]
+    pub(crate) struct ChromIndex {
+        lines: Vec<BgLine>,
+        max_stop: Vec<Coord>,
+    }
+
+    impl ChromIndex {
+        pub(crate) fn overlapping<'b>(&'b self, a: &ChromSeg) -> impl Iterator<Item = &'b BgLine> + 'b {
+            let first = self.max_stop.partition_point(|stop| *stop <= a.start);
+            let last = self.lines.partition_point(|b| b.coords.start < a.stop);
+            let start = a.start;
+            self.lines[first..last.max(first)].iter().filter(move |b| b.coords.stop > start)
+        }
+    }
+
+    pub(crate) fn read_index(fname: &str, renamer: Option<&Rc<Renamer>>) -> Result<HashMap<String, ChromIndex>, String> {
+        let mut bg = BgIterator::open(fname, renamer)?;
+        let mut index: HashMap<String, ChromIndex> = HashMap::new();
+        while let Some(line) = bg.try_next()? {
+            index.entry(line.coords.chrom.clone()).or_default().lines.push(line);
+        }
+        for chrom in index.values_mut() {
+            chrom.lines.sort_by_key(|b| b.coords.start);
+            let mut max_stop = 0;
+            chrom.max_stop = chrom.lines.iter().map(|b| {
+                max_stop = max_stop.max(b.coords.stop);
+                max_stop
+            }).collect();
+        }
+        Ok(index)
+    }
+
+    //report the overlaps between the intervals in a_file and those in each of b_files
+    pub fn intersect_bed<W: Write>(a_file: &str, b_files: &[&str], config: &IntersectConfig, output: &mut W) -> Result<(), String> {
+        match config.sorted {
+            Some(ref order) => {
+                let mut a_reader = SortedReader::open(a_file, order, config.bounds, config.renamer.as_ref())?;
+                let mut windows = b_files.iter().map(|fname| Window::new(SortedReader::open(fname, order, config.bounds, config.renamer.as_ref())?))
+                                                .collect::<Result<Vec<Window>, String>>()?;
+                while let Some(a) = a_reader.try_next()? {
+                    for window in windows.iter_mut() {
+                        window.advance(&a.coords)?;
+                    }
+                    let hits: Vec<(usize, &BgLine)> = windows.iter().enumerate().flat_map(|(file, window)| {
+                        window.lines.iter().filter(|b| accepts(&a, b, config)).map(move |b| (file, b))
+                    }).collect();
+                    report(output, &a, &hits, config);
+                }
+            },
+            None => {
+                let indexes = b_files.iter().map(|fname| read_index(fname, config.renamer.as_ref())).collect::<Result<Vec<_>, String>>()?;
+                let mut a_reader = BgIterator::open(a_file, config.renamer.as_ref())?;
+                while let Some(a) = a_reader.try_next()? {
+                    let hits: Vec<(usize, &BgLine)> = indexes.iter().enumerate().flat_map(|(file, index)| {
+                        index.get(&a.coords.chrom).into_iter().flat_map(|chrom| chrom.overlapping(&a.coords))
+                                                  .filter(|b| accepts(&a, b, config)).map(move |b| (file, b))
+                    }).collect();
+                    report(output, &a, &hits, config);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    pub fn intersect_main(a_file: &str, b_files: &[&str], config: IntersectConfig) -> Result<(), String> {
+        run_to_stdout(|output| intersect_bed(a_file, b_files, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_intersect {
+        use super::*;
+        use super::super::chrom_alias::AliasTable;
+        use super::super::output::output_lines;
+
+        //run in both modes, checking that they agree
+        fn intersected(b_files: &[&str], config: IntersectConfig) -> Result<Vec<String>, String> {
+            let run = |b_files: &[&str], config: &IntersectConfig| {
+                output_lines(|output| intersect_bed("test/intersect/a.bed", b_files, config, output))
+            };
+            let sorted = run(b_files, &IntersectConfig{sorted: Some(ChromOrder::Lexicographic), ..config.clone()})?;
+            let unsorted = run(b_files, &config)?;
+            assert_eq!(sorted, unsorted);
+            Ok(sorted)
+        }
+
+        fn names(lines: &[String]) -> Vec<String> {
+            lines.iter().map(|line| line.split('\t').nth(3).unwrap().to_string()).collect()
+        }
+
+        #[test]
+        fn intersect_defaults() {
+            let lines = intersected(&["test/intersect/b.bed"], IntersectConfig::default()).unwrap();
+            assert_eq!(lines, vec!["chr1\t120\t180\ta1\t0\t+",
+                                   "chr1\t190\t200\ta1\t0\t+",
+                                   "chr1\t150\t180\ta2\t0\t-",
+                                   "chr1\t190\t250\ta2\t0\t-",
+                                   "chr2\t55\t60\ta4\t0\t+"]);
+        }
+
+        #[test]
+        fn intersect_renamed() {
+            //b.bed with Ensembl names, renamed to match a.bed
+            let table = AliasTable::from_file("test/alias/hg38.chromAlias.txt").unwrap();
+            let renamer = Rc::new(Renamer::new(table, "ucsc").unwrap());
+            let config = IntersectConfig{renamer: Some(renamer), ..IntersectConfig::default()};
+            assert_eq!(intersected(&["test/alias/b.ensembl.bed"], config).unwrap(),
+                       intersected(&["test/intersect/b.bed"], IntersectConfig::default()).unwrap());
+        }
+
+        #[test]
+        fn intersect_reports() {
+            let config = IntersectConfig{report: Report::Unique, ..IntersectConfig::default()};
+            assert_eq!(names(&intersected(&["test/intersect/b.bed"], config).unwrap()), vec!["a1", "a2", "a4"]);
+            let config = IntersectConfig{report: Report::Missing, ..IntersectConfig::default()};
+            assert_eq!(names(&intersected(&["test/intersect/b.bed"], config).unwrap()), vec!["a3"]);
+            let config = IntersectConfig{report: Report::Count, ..IntersectConfig::default()};
+            let counts: Vec<String> = intersected(&["test/intersect/b.bed"], config).unwrap().iter()
+                                                 .map(|line| line.rsplit('\t').next().unwrap().to_string()).collect();
+            assert_eq!(counts, vec!["2", "2", "0", "1"]);
+        }
+
+        #[test]
+        fn intersect_write_both() {
+            let config = IntersectConfig{write_a: true, write_b: true, ..IntersectConfig::default()};
+            let lines = intersected(&["test/intersect/b.bed"], config).unwrap();
+            assert_eq!(lines[0], "chr1\t100\t200\ta1\t0\t+\tchr1\t120\t180\tb1\t0\t+");
+            //several B files are labelled
+            let config = IntersectConfig{write_overlap: true, labels: vec!["b".to_string(), "c".to_string()], ..IntersectConfig::default()};
+            let lines = intersected(&["test/intersect/b.bed", "test/intersect/c.bed"], config).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t200\ta1\t0\t+\tb\tchr1\t120\t180\tb1\t0\t+\t60",
+                                   "chr1\t100\t200\ta1\t0\t+\tb\tchr1\t190\t250\tb2\t0\t-\t10",
+                                   "chr1\t100\t200\ta1\t0\t+\tc\tchr1\t0\t1050\tc1\t0\t+\t100",
+                                   "chr1\t150\t400\ta2\t0\t-\tb\tchr1\t120\t180\tb1\t0\t+\t30",
+                                   "chr1\t150\t400\ta2\t0\t-\tb\tchr1\t190\t250\tb2\t0\t-\t60",
+                                   "chr1\t150\t400\ta2\t0\t-\tc\tchr1\t0\t1050\tc1\t0\t+\t250",
+                                   "chr1\t1000\t1100\ta3\t0\t+\tc\tchr1\t0\t1050\tc1\t0\t+\t50",
+                                   "chr2\t50\t60\ta4\t0\t+\tb\tchr2\t55\t100\tb4\t0\t-\t5"]);
+        }
+
+        fn pairs(config: IntersectConfig) -> Vec<String> {
+            let config = IntersectConfig{write_a: true, write_b: true, ..config};
+            intersected(&["test/intersect/b.bed"], config).unwrap().iter().map(|line| {
+                let cols: Vec<&str> = line.split('\t').collect();
+                format!("{}-{}", cols[3], cols[9])
+            }).collect()
+        }
+
+        #[test]
+        fn intersect_fractions() {
+            assert_eq!(pairs(IntersectConfig{min_frac_a: 0.5, ..IntersectConfig::default()}), vec!["a1-b1", "a4-b4"]);
+            assert_eq!(pairs(IntersectConfig{min_frac_b: 0.5, ..IntersectConfig::default()}), vec!["a1-b1", "a2-b1", "a2-b2"]);
+            assert_eq!(pairs(IntersectConfig{min_frac_a: 0.5, reciprocal: true, ..IntersectConfig::default()}), vec!["a1-b1"]);
+        }
+
+        #[test]
+        fn intersect_strands() {
+            assert_eq!(pairs(IntersectConfig{strand: Strandedness::Same, ..IntersectConfig::default()}), vec!["a1-b1", "a2-b2"]);
+            assert_eq!(pairs(IntersectConfig{strand: Strandedness::Opposite, ..IntersectConfig::default()}), vec!["a1-b2", "a2-b1", "a4-b4"]);
+        }
+
+        #[test]
+        fn intersect_unsorted() {
+            let mut output = Vec::new();
+            intersect_bed("test/intersect/a.bed", &["test/intersect/b.unsorted.bed"], &IntersectConfig::default(), &mut output).unwrap();
+            let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+            assert_eq!(lines.len(), 5);
+            let config = IntersectConfig{sorted: Some(ChromOrder::Lexicographic), ..IntersectConfig::default()};
+            let expect = String::from("Error: Sorted input specified, but the file test/intersect/b.unsorted.bed has the following out of order record\nchr1\t500\t600\tb3\t0\t+");
+            if let Err(msg) = intersect_bed("test/intersect/a.bed", &["test/intersect/b.unsorted.bed"], &config, &mut Vec::new()) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from intersect_bed(), received Ok(_) instead");
+            }
+        }
+    }
+}
+
+pub mod complement {
+    use std::io::Write;
+    use std::rc::Rc;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::BgIterator;
+    use super::chrom_alias::Renamer;
+    use super::chrom_sizes::{BoundsCheck, Genome, OutOfBounds};
+    use super::output::{run_to_stdout, write_line};
+    use super::chrom_sizes::ChromOrder;
+    use super::sort::SortedReader;
+
+    //walks through the chromosomes of a genome in order, writing the
+    //regions that are not covered
+    struct GenomeWalk<'a> {
+        genome: &'a Genome,
+        //the current chromosome, and the end of the coverage on it
+        id: usize,
+        covered: Coord,
+    }
+
+    impl<'a> GenomeWalk<'a> {
+        //cover a segment (which must not be on an earlier chromosome or
+        //start before the previous one), writing the gap before it
+        fn cover<W: Write>(&mut self, output: &mut W, id: usize, start: Coord, stop: Coord) {
+            while self.id < id {
+                self.finish_chrom(output);
+            }
+            if start > self.covered {
+                self.write_gap(output, start);
+            }
+            self.covered = self.covered.max(stop);
+        }
+
+        //write the rest of the current chromosome and move on to the next
+        fn finish_chrom<W: Write>(&mut self, output: &mut W) {
+            let size = self.genome.size(self.id);
+            if size > self.covered {
+                self.write_gap(output, size);
+            }
+            self.id += 1;
+            self.covered = 0;
+        }
+
+        fn write_gap<W: Write>(&self, output: &mut W, stop: Coord) {
+            let gap = ChromSeg{chrom: self.genome.name(self.id).to_string(), start: self.covered, stop};
+            write_line(output, &gap);
+        }
+    }
+
+    //write the regions of the genome that are not covered by the intervals
+    //in filename, which must be sorted in the order of the genome
+    //chromosomes that are missing from the input are written in full
+    pub fn complement_bed<W: Write>(filename: &str, genome: Rc<Genome>, bounds: OutOfBounds, renamer: Option<&Rc<Renamer>>, output: &mut W) -> Result<(), String> {
+        let bg = BgIterator::open(filename, renamer)?
+                                          .with_bounds(BoundsCheck::new(genome.clone(), bounds));
+        let order = ChromOrder::Genome(genome.clone());
+        let mut reader = SortedReader::new(bg, filename, &order);
+        let mut walk = GenomeWalk{genome: &genome, id: 0, covered: 0};
+        while let Some(line) = reader.try_next()? {
+            //the bounds check has rejected or dropped the unknown chromosomes
+            let id = genome.id(&line.coords.chrom).unwrap();
+            walk.cover(output, id, line.coords.start, line.coords.stop);
+        }
+        while walk.id < genome.len() {
+            walk.finish_chrom(output);
+        }
+        Ok(())
+    }
+
+    pub fn complement_main(filename: &str, genome: Genome, bounds: OutOfBounds, renamer: Option<Rc<Renamer>>) -> Result<(), String> {
+        run_to_stdout(|output| complement_bed(filename, Rc::new(genome), bounds, renamer.as_ref(), output))
+    }
+
+    #[cfg(test)]
+    mod test_complement {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn complemented(filename: &str, bounds: OutOfBounds) -> Result<Vec<String>, String> {
+            let genome = Genome::from_file("test/complement/genome.chrom.sizes").unwrap();
+            output_lines(|output| complement_bed(filename, Rc::new(genome), bounds, None, output))
+        }
+
+        #[test]
+        fn complement_genome() {
+            let lines = complemented("test/complement/input.bed", OutOfBounds::Reject).unwrap();
+            assert_eq!(lines, vec!["chr1\t200\t300",
+                                   "chr1\t400\t1000",
+                                   "chr2\t0\t100",
+                                   "chr3\t0\t300",
+                                   "chrM\t0\t10",
+                                   "chrM\t20\t100"]);
+        }
+
+        #[test]
+        fn complement_bounds() {
+            let expect = String::from("Error in 'test/complement/unknown.bed', line 2: unknown chromosome 'chrZ'");
+            if let Err(msg) = complemented("test/complement/unknown.bed", OutOfBounds::Reject) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from complement_bed(), received Ok(_) instead");
+            }
+            let lines = complemented("test/complement/unknown.bed", OutOfBounds::Clip).unwrap();
+            assert_eq!(lines, vec!["chr1\t100\t1000", "chr2\t0\t400", "chr3\t0\t300", "chrM\t0\t100"]);
+        }
+
+        #[test]
+        fn complement_unsorted() {
+            let expect = String::from("Error: Sorted input specified, but the file test/complement/unsorted.bed has the following out of order record\nchr1\t0\t100");
+            if let Err(msg) = complemented("test/complement/unsorted.bed", OutOfBounds::Reject) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from complement_bed(), received Ok(_) instead");
+            }
+        }
+    }
+}
+
+pub mod genomecov {
+    use std::collections::BTreeMap;
+    use std::io::Write;
+    use std::rc::Rc;
+    use super::chrom_geo::Coord;
+    use super::bedgraph::BgIterator;
+    use super::chrom_alias::Renamer;
+    use super::chrom_sizes::{BoundsCheck, Genome, OutOfBounds};
+    use super::output::{run_to_stdout, write_line};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CovOutput {
+        //the number of bases at each depth, per chromosome and for the genome
+        Histogram,
+        //-bg: bedGraph of the covered regions
+        BedGraph,
+        //-bga: bedGraph including the regions with no coverage
+        BedGraphAll,
+        //-d: the depth of every base (1-based)
+        PerBase,
+    }
+
+    //which part of each interval is counted
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CountEnd {
+        Whole,
+        //-5: only the 5' end (the start, or the stop on the '-' strand)
+        Five,
+        //-3: only the 3' end
+        Three,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct CovConfig {
+        pub output: CovOutput,
+        //multiplies the depths in the bedGraph and per-base output
+        pub scale: f64,
+        //only count intervals on this strand (column 6)
+        pub strand: Option<char>,
+        pub end: CountEnd,
+        pub bounds: OutOfBounds,
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for CovConfig {
+        fn default() -> CovConfig {
+            CovConfig{output: CovOutput::Histogram, scale: 1.0, strand: None, end: CountEnd::Whole, bounds: OutOfBounds::Reject, renamer: None}
+        }
+    }
+
+    //the depth changes (+1 at a start, -1 at a stop) on each chromosome of the genome
+    fn read_events(filename: &str, genome: &Rc<Genome>, config: &CovConfig) -> Result<Vec<Vec<(Coord, i64)>>, String> {
+        let mut bg = BgIterator::open(filename, config.renamer.as_ref())?
+                                              .with_bounds(BoundsCheck::new(genome.clone(), config.bounds));
+        let mut events = vec![Vec::new(); genome.len()];
+        while let Some(line) = bg.try_next()? {
+            let strand = line.strand().and_then(|strand| strand.chars().next());
+            if config.strand.is_some() && strand != config.strand {
+                continue;
+            }
+            let (start, stop) = (line.coords.start, line.coords.stop);
+            if start >= stop {
+                continue;
+            }
+            let reverse = strand == Some('-');
+            let (start, stop) = match config.end {
+                CountEnd::Whole => (start, stop),
+                CountEnd::Five if !reverse => (start, start + 1),
+                CountEnd::Three if reverse => (start, start + 1),
+                _ => (stop - 1, stop),
+            };
+            //the bounds check has rejected or dropped the unknown chromosomes
+            let id = genome.id(&line.coords.chrom).unwrap();
+            events[id].push((start, 1));
+            events[id].push((stop, -1));
+        }
+        Ok(events)
+    }
+
+    //split a chromosome into runs of the same depth, as (start, stop, depth)
+    pub(crate) fn depth_runs(events: &mut [(Coord, i64)], size: Coord) -> Vec<(Coord, Coord, i64)> {
+        events.sort_unstable();
+        let mut runs: Vec<(Coord, Coord, i64)> = Vec::new();
+        let mut pos = 0;
+        let mut depth = 0;
+        let mut i = 0;
+        while pos < size {
+            //apply every change at this position
+            while i < events.len() && events[i].0 <= pos {
+                depth += events[i].1;
+                i += 1;
+            }
+            let next = if i < events.len() { events[i].0.min(size) } else { size };
+            match runs.last_mut() {
+                Some(last) if last.2 == depth => last.1 = next,
+                _ => runs.push((pos, next, depth)),
+            }
+            pos = next;
+        }
+        runs
+    }
+
+    fn format_depth(depth: i64, scale: f64) -> String {
+        if scale == 1.0 {
+            depth.to_string()
+        } else {
+            (depth as f64 * scale).to_string()
+        }
+    }
+
+    //write the histogram lines for a set of bases: depth, number of bases
+    //at that depth, total size and the fraction at that depth
+    pub(crate) fn write_histogram<W: Write>(output: &mut W, name: &str, histogram: &BTreeMap<i64, Coord>, size: Coord) {
+        for (depth, bases) in histogram {
+            write_line(output, &format!("{}\t{}\t{}\t{}\t{}", name, depth, bases, size, *bases as f64 / size as f64));
+        }
+    }
+
+    //compute the coverage of the genome by the intervals in filename (which
+    //need not be sorted)
+    //bedGraph and per-base output is sorted lexicographically by chromosome,
+    //as unionbedg expects, and histograms follow the order of the genome
+    pub fn genomecov_bed<W: Write>(filename: &str, genome: Rc<Genome>, config: &CovConfig, output: &mut W) -> Result<(), String> {
+        let mut events = read_events(filename, &genome, config)?;
+        let mut ids: Vec<usize> = (0..genome.len()).collect();
+        if config.output != CovOutput::Histogram {
+            ids.sort_by(|a, b| genome.name(*a).cmp(genome.name(*b)));
+        }
+        let mut genome_hist: BTreeMap<i64, Coord> = BTreeMap::new();
+        for id in ids {
+            let (name, size) = (genome.name(id), genome.size(id));
+            let runs = depth_runs(&mut events[id], size);
+            match config.output {
+                CovOutput::Histogram => {
+                    let mut histogram: BTreeMap<i64, Coord> = BTreeMap::new();
+                    for (start, stop, depth) in runs {
+                        *histogram.entry(depth).or_insert(0) += stop - start;
+                        *genome_hist.entry(depth).or_insert(0) += stop - start;
+                    }
+                    write_histogram(output, name, &histogram, size);
+                },
+                CovOutput::BedGraph | CovOutput::BedGraphAll => {
+                    for (start, stop, depth) in runs {
+                        if depth > 0 || config.output == CovOutput::BedGraphAll {
+                            write_line(output, &format!("{}\t{}\t{}\t{}", name, start, stop, format_depth(depth, config.scale)));
+                        }
+                    }
+                },
+                CovOutput::PerBase => {
+                    for (start, stop, depth) in runs {
+                        let depth = format_depth(depth, config.scale);
+                        for pos in start..stop {
+                            write_line(output, &format!("{}\t{}\t{}", name, pos + 1, depth));
+                        }
+                    }
+                },
+            }
+            events[id] = Vec::new();
+        }
+        if config.output == CovOutput::Histogram {
+            write_histogram(output, "genome", &genome_hist, genome.total_len());
+        }
+        Ok(())
+    }
+
+    pub fn genomecov_main(filename: &str, genome: Genome, config: CovConfig) -> Result<(), String> {
+        run_to_stdout(|output| genomecov_bed(filename, Rc::new(genome), &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_genomecov {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn coverage(config: CovConfig) -> Vec<String> {
+            let genome = Genome::from_file("test/genomecov/genome.chrom.sizes").unwrap();
+            output_lines(|output| genomecov_bed("test/genomecov/reads.bed", Rc::new(genome), &config, output)).unwrap()
+        }
+
+        #[test]
+        fn genomecov_histogram() {
+            let lines = coverage(CovConfig::default());
+            assert_eq!(lines, vec!["chrA\t0\t60\t100\t0.6".to_string(),
+                                   "chrA\t1\t30\t100\t0.3".to_string(),
+                                   "chrA\t2\t10\t100\t0.1".to_string(),
+                                   "chrB\t0\t40\t50\t0.8".to_string(),
+                                   "chrB\t1\t10\t50\t0.2".to_string(),
+                                   "chr2\t0\t20\t20\t1".to_string(),
+                                   format!("genome\t0\t120\t170\t{}", 120.0 / 170.0),
+                                   format!("genome\t1\t40\t170\t{}", 40.0 / 170.0),
+                                   format!("genome\t2\t10\t170\t{}", 10.0 / 170.0)]);
+        }
+
+        #[test]
+        fn genomecov_bedgraph() {
+            let lines = coverage(CovConfig{output: CovOutput::BedGraph, ..CovConfig::default()});
+            assert_eq!(lines, vec!["chrA\t10\t20\t1", "chrA\t20\t30\t2", "chrA\t30\t50\t1", "chrB\t0\t10\t1"]);
+            let lines = coverage(CovConfig{output: CovOutput::BedGraphAll, ..CovConfig::default()});
+            assert_eq!(lines, vec!["chr2\t0\t20\t0",
+                                   "chrA\t0\t10\t0",
+                                   "chrA\t10\t20\t1",
+                                   "chrA\t20\t30\t2",
+                                   "chrA\t30\t50\t1",
+                                   "chrA\t50\t100\t0",
+                                   "chrB\t0\t10\t1",
+                                   "chrB\t10\t50\t0"]);
+            let lines = coverage(CovConfig{output: CovOutput::BedGraph, scale: 0.5, ..CovConfig::default()});
+            assert_eq!(lines, vec!["chrA\t10\t20\t0.5", "chrA\t20\t30\t1", "chrA\t30\t50\t0.5", "chrB\t0\t10\t0.5"]);
+        }
+
+        #[test]
+        fn genomecov_ends() {
+            let lines = coverage(CovConfig{output: CovOutput::BedGraph, end: CountEnd::Five, ..CovConfig::default()});
+            assert_eq!(lines, vec!["chrA\t10\t11\t1", "chrA\t39\t41\t1", "chrB\t9\t10\t1"]);
+            let lines = coverage(CovConfig{output: CovOutput::BedGraph, end: CountEnd::Three, ..CovConfig::default()});
+            assert_eq!(lines, vec!["chrA\t20\t21\t1", "chrA\t29\t30\t1", "chrA\t49\t50\t1", "chrB\t0\t1\t1"]);
+            let lines = coverage(CovConfig{output: CovOutput::BedGraph, strand: Some('+'), ..CovConfig::default()});
+            assert_eq!(lines, vec!["chrA\t10\t30\t1", "chrA\t40\t50\t1"]);
+        }
+
+        #[test]
+        fn genomecov_per_base() {
+            let lines = coverage(CovConfig{output: CovOutput::PerBase, ..CovConfig::default()});
+            assert_eq!(lines.len(), 170);
+            assert_eq!(lines[0], "chr2\t1\t0");
+            assert_eq!(lines[30], "chrA\t11\t1");
+            assert_eq!(lines[40], "chrA\t21\t2");
+        }
+    }
+}
+
+pub mod resize {
+    use std::io::Write;
+    use std::rc::Rc;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::chrom_alias::Renamer;
+    use super::chrom_sizes::{BoundsCheck, Genome, OutOfBounds};
+    use super::output::{run_to_stdout, write_line};
+
+    //how to change each interval
+    //the amounts are in bp, or fractions of the interval's length if
+    //ResizeConfig.pct is set
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Resize {
+        //extend the start by left and the stop by right
+        Slop{left: f64, right: f64},
+        //report the left bases before the start and the right bases after
+        //the stop, instead of the interval
+        Flank{left: f64, right: f64},
+        //move intervals on the '-' strand by minus, and the others by plus
+        Shift{plus: f64, minus: f64},
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ResizeConfig {
+        pub resize: Resize,
+        pub pct: bool,
+        //swap left and right for intervals on the '-' strand (column 6)
+        pub strand: bool,
+        pub bounds: OutOfBounds,
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl ResizeConfig {
+        pub fn new(resize: Resize) -> ResizeConfig {
+            ResizeConfig{resize, pct: false, strand: false, bounds: OutOfBounds::Reject, renamer: None}
+        }
+
+        //an amount in bp for an interval
+        fn bases(&self, amount: f64, coords: &ChromSeg) -> i64 {
+            if self.pct {
+                (amount * (coords.stop - coords.start) as f64).round() as i64
+            } else {
+                amount as i64
+            }
+        }
+    }
+
+    //keep a position within a chromosome
+    fn clamp(pos: i64, size: Coord) -> Coord {
+        pos.max(0).min(size as i64) as Coord
+    }
+
+    //the intervals made from one input line, clipped to the chromosome
+    fn resize_line(line: &BgLine, size: Coord, config: &ResizeConfig) -> Vec<ChromSeg> {
+        let coords = &line.coords;
+        let (start, stop) = (coords.start as i64, coords.stop as i64);
+        let seg = |start: Coord, stop: Coord| ChromSeg{chrom: coords.chrom.clone(), start, stop};
+        let sides = |left: f64, right: f64| {
+            let (left, right) = (config.bases(left, coords), config.bases(right, coords));
+            if config.strand && line.strand() == Some("-") { (right, left) } else { (left, right) }
+        };
+        match config.resize {
+            Resize::Slop{left, right} => {
+                let (left, right) = sides(left, right);
+                vec![seg(clamp(start - left, size), clamp(stop + right, size))]
+            },
+            Resize::Flank{left, right} => {
+                let (left, right) = sides(left, right);
+                let mut flanks = Vec::new();
+                if left > 0 && start > 0 {
+                    flanks.push(seg(clamp(start - left, size), clamp(start, size)));
+                }
+                if right > 0 && (stop as Coord) < size {
+                    flanks.push(seg(clamp(stop, size), clamp(stop + right, size)));
+                }
+                flanks
+            },
+            Resize::Shift{plus, minus} => {
+                let shift = config.bases(if line.strand() == Some("-") { minus } else { plus }, coords);
+                //an interval shifted off the chromosome keeps its last base
+                let new_start = clamp(start + shift, size).min(size.saturating_sub(1));
+                let new_stop = clamp(stop + shift, size).max(new_start + 1).min(size);
+                vec![seg(new_start, new_stop)]
+            },
+        }
+    }
+
+    //resize the intervals in filename, keeping their extra columns
+    pub fn resize_bed<W: Write>(filename: &str, genome: Rc<Genome>, config: &ResizeConfig, output: &mut W) -> Result<(), String> {
+        let mut bg = BgIterator::open(filename, config.renamer.as_ref())?
+                                              .with_bounds(BoundsCheck::new(genome.clone(), config.bounds));
+        while let Some(line) = bg.try_next()? {
+            //the bounds check has rejected or dropped the unknown chromosomes
+            let size = genome.size(genome.id(&line.coords.chrom).unwrap());
+            for coords in resize_line(&line, size, config) {
+                write_line(output, &BgLine{coords, data: line.data.clone()});
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resize_main(filename: &str, genome: Genome, config: ResizeConfig) -> Result<(), String> {
+        run_to_stdout(|output| resize_bed(filename, Rc::new(genome), &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_resize {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn resized(config: ResizeConfig) -> Vec<String> {
+            let genome = Genome::from_file("test/resize/genome.chrom.sizes").unwrap();
+            let lines = output_lines(|output| resize_bed("test/resize/input.bed", Rc::new(genome), &config, output)).unwrap();
+            lines.iter().map(|line| {
+                let cols: Vec<&str> = line.split('\t').collect();
+                format!("{}:{}-{}", cols[3], cols[1], cols[2])
+            }).collect()
+        }
+
+        #[test]
+        fn slop() {
+            assert_eq!(resized(ResizeConfig::new(Resize::Slop{left: 20.0, right: 20.0})),
+                       vec!["a:80-220", "b:480-620", "c:930-1000", "d:0-80"]);
+            let config = ResizeConfig{strand: true, ..ResizeConfig::new(Resize::Slop{left: 20.0, right: 5.0})};
+            assert_eq!(resized(config), vec!["a:80-205", "b:495-620", "c:930-1000", "d:5-80"]);
+            let config = ResizeConfig{pct: true, ..ResizeConfig::new(Resize::Slop{left: 0.1, right: 0.1})};
+            assert_eq!(resized(config), vec!["a:90-210", "b:490-610", "c:945-1000", "d:5-65"]);
+        }
+
+        #[test]
+        fn flank() {
+            assert_eq!(resized(ResizeConfig::new(Resize::Flank{left: 30.0, right: 30.0})),
+                       vec!["a:70-100", "a:200-230", "b:470-500", "b:600-630", "c:920-950", "d:0-10", "d:60-90"]);
+            let config = ResizeConfig{strand: true, ..ResizeConfig::new(Resize::Flank{left: 30.0, right: 0.0})};
+            assert_eq!(resized(config), vec!["a:70-100", "b:600-630", "c:920-950", "d:60-90"]);
+        }
+
+        #[test]
+        fn shift() {
+            assert_eq!(resized(ResizeConfig::new(Resize::Shift{plus: 100.0, minus: -100.0})),
+                       vec!["a:200-300", "b:400-500", "c:999-1000", "d:0-1"]);
+            let config = ResizeConfig{pct: true, ..ResizeConfig::new(Resize::Shift{plus: 0.5, minus: 0.5})};
+            assert_eq!(resized(config), vec!["a:150-250", "b:550-650", "c:975-1000", "d:35-85"]);
+        }
+
+        #[test]
+        fn extra_columns() {
+            let genome = Genome::from_file("test/resize/genome.chrom.sizes").unwrap();
+            let config = ResizeConfig::new(Resize::Slop{left: 1.0, right: 1.0});
+            let lines = output_lines(|output| resize_bed("test/resize/input.bed", Rc::new(genome), &config, output)).unwrap();
+            assert_eq!(lines[0], "chr1\t99\t201\ta\t0\t+");
+        }
+    }
+}
+
+pub mod closest {
+    use std::cmp::Ordering;
+    use std::io::Write;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::BgLine;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use super::chrom_sizes::{ChromOrder, OutOfBounds};
+    use super::sort::SortedReader;
+    use std::rc::Rc;
+
+    //how signed distances are oriented (-D); negative distances are upstream
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Orientation {
+        //B is upstream if it comes before A on the chromosome
+        Ref,
+        //upstream with respect to the strand of A (column 6)
+        A,
+        //A is upstream of B, with respect to the strand of B
+        B,
+    }
+
+    impl Orientation {
+        pub fn parse(mode: &str) -> Result<Orientation, String> {
+            match mode {
+                "ref" => Ok(Orientation::Ref),
+                "a" => Ok(Orientation::A),
+                "b" => Ok(Orientation::B),
+                _ => Err(format!("Invalid distance mode '{}'. Expected one of 'ref', 'a', 'b'", mode)),
+            }
+        }
+    }
+
+    //which of several equally close B intervals to report (-t)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Ties {
+        All,
+        First,
+        Last,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ClosestConfig {
+        //-d: write the distance after the B interval
+        pub report_distance: bool,
+        //-D: write signed distances instead
+        pub signed: Option<Orientation>,
+        //-k: the number of closest intervals to report
+        pub k: usize,
+        //-io, -iu, -id: skip overlapping, upstream or downstream intervals
+        //(up- and downstream follow the orientation of 'signed')
+        pub ignore_overlaps: bool,
+        pub ignore_upstream: bool,
+        pub ignore_downstream: bool,
+        pub ties: Ties,
+        //the order that both files are sorted in
+        pub order: ChromOrder,
+        //for the intervals outside the genome, if the order is a genome's
+        pub bounds: OutOfBounds,
+        //renames the chromosomes of both files
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for ClosestConfig {
+        fn default() -> ClosestConfig {
+            ClosestConfig{
+                report_distance: false,
+                signed: None,
+                k: 1,
+                ignore_overlaps: false,
+                ignore_upstream: false,
+                ignore_downstream: false,
+                ties: Ties::All,
+                order: ChromOrder::Lexicographic,
+                bounds: OutOfBounds::Reject,
+                renamer: None,
+            }
+        }
+    }
+
+    //the distance from a to b: 0 if they overlap, 1 if they are book-ended,
+    //and negative if b comes first
+    fn ref_distance(a: &ChromSeg, b: &ChromSeg) -> i64 {
+        if b.stop <= a.start && b.stop < a.stop {
+            -((a.start - b.stop) as i64 + 1)
+        } else if b.start >= a.stop && b.start > a.start {
+            (b.start - a.stop) as i64 + 1
+        } else {
+            0
+        }
+    }
+
+    fn signed_distance(a: &BgLine, b: &BgLine, orientation: Orientation) -> i64 {
+        let distance = ref_distance(&a.coords, &b.coords);
+        match orientation {
+            Orientation::Ref => distance,
+            Orientation::A => if a.strand() == Some("-") { -distance } else { distance },
+            Orientation::B => if b.strand() == Some("-") { distance } else { -distance },
+        }
+    }
+
+    //the B intervals on the current chromosome that may be closest to the
+    //current A interval or a later one
+    struct Window<'a> {
+        reader: SortedReader<'a>,
+        next: Option<BgLine>,
+        //the intervals that end before the current A interval, in file order
+        upstream: Vec<BgLine>,
+        //the size that upstream is pruned back from
+        upstream_limit: usize,
+        //the intervals that overlap or follow the current A interval, in file order
+        lines: Vec<BgLine>,
+        //the number of extra columns in B, for the lines with no closest interval
+        extra_cols: usize,
+    }
+
+    impl<'a> Window<'a> {
+        fn new(mut reader: SortedReader<'a>) -> Result<Window<'a>, String> {
+            let next = reader.try_next()?;
+            let extra_cols = next.as_ref().and_then(|b| b.data.as_ref()).map_or(0, |data| data.split('\t').count());
+            Ok(Window{reader, next, upstream: Vec::new(), upstream_limit: 0, lines: Vec::new(), extra_cols})
+        }
+
+        //an interval before A cannot be closer to a later interval than the
+        //k intervals that end after it (and before A), so keep only the k
+        //largest stops and the ones tied with them
+        fn prune(&mut self, k: usize) {
+            if self.upstream.len() > k {
+                let mut stops: Vec<Coord> = self.upstream.iter().map(|b| b.coords.stop).collect();
+                stops.sort_unstable_by(|x, y| y.cmp(x));
+                let min_stop = stops[k - 1];
+                self.upstream.retain(|b| b.coords.stop >= min_stop);
+            }
+            //pruning again only once the size has doubled keeps it linear
+            self.upstream_limit = 2 * self.upstream.len().max(k);
+        }
+
+        fn push_upstream(&mut self, b: BgLine, k: usize) {
+            self.upstream.push(b);
+            if self.upstream.len() > self.upstream_limit {
+                self.prune(k);
+            }
+        }
+
+        fn advance(&mut self, a: &ChromSeg, k: usize) -> Result<(), String> {
+            if let Some(b) = self.upstream.first().or_else(|| self.lines.first()) {
+                if b.coords.chrom != a.chrom {
+                    self.upstream.clear();
+                    self.lines.clear();
+                }
+            }
+            let (passed, lines): (Vec<BgLine>, Vec<BgLine>) = self.lines.drain(..).partition(|b| b.coords.stop <= a.start);
+            self.lines = lines;
+            if !passed.is_empty() {
+                for b in passed {
+                    self.push_upstream(b, k);
+                }
+                //back into file order, as B is sorted by start
+                self.upstream.sort_by_key(|b| b.coords.start);
+            }
+            while let Some(b) = self.next.take() {
+                match self.reader.order().compare_chroms(&b.coords.chrom, &a.chrom) {
+                    Ordering::Less => (),
+                    Ordering::Greater => {
+                        self.next = Some(b);
+                        break;
+                    },
+                    Ordering::Equal if b.coords.stop <= a.start => self.push_upstream(b, k),
+                    Ordering::Equal if b.coords.start < a.stop => self.lines.push(b),
+                    Ordering::Equal => {
+                        //read downstream intervals until there are k of them,
+                        //and all of the ones tied with the k-th (they are
+                        //sorted by start, so they end the window)
+                        let downstream = self.lines.iter().rev().take_while(|b| b.coords.start >= a.stop).count();
+                        let downstream = &self.lines[self.lines.len() - downstream..];
+                        if downstream.len() >= k && b.coords.start > downstream[k - 1].coords.start {
+                            self.next = Some(b);
+                            break;
+                        }
+                        self.lines.push(b);
+                    },
+                }
+                self.next = self.reader.try_next()?;
+            }
+            self.prune(k);
+            Ok(())
+        }
+
+        //the intervals that may be closest to the current A interval, in
+        //file order as far as ties are concerned
+        fn candidates(&self) -> impl Iterator<Item = &BgLine> {
+            self.upstream.iter().chain(self.lines.iter())
+        }
+    }
+
+    //choose the closest of the candidates (pairs of signed distance and
+    //interval, in file order)
+    fn choose<'b>(mut candidates: Vec<(i64, &'b BgLine)>, config: &ClosestConfig) -> Vec<(i64, &'b BgLine)> {
+        candidates.sort_by_key(|(distance, _)| distance.abs());
+        let mut chosen = Vec::new();
+        let mut i = 0;
+        while i < candidates.len() && chosen.len() < config.k {
+            let tied = candidates[i..].iter().take_while(|(distance, _)| distance.abs() == candidates[i].0.abs()).count();
+            let group = &candidates[i..i + tied];
+            let wanted = config.k - chosen.len();
+            match config.ties {
+                Ties::All => chosen.extend_from_slice(group),
+                Ties::First => chosen.extend_from_slice(&group[..wanted.min(tied)]),
+                Ties::Last => chosen.extend_from_slice(&group[tied - wanted.min(tied)..]),
+            }
+            i += tied;
+        }
+        chosen
+    }
+
+    //report the closest interval in b_file to each interval in a_file,
+    //sweeping through both files, which must be sorted
+    pub fn closest_bed<W: Write>(a_file: &str, b_file: &str, config: &ClosestConfig, output: &mut W) -> Result<(), String> {
+        let mut a_reader = SortedReader::open(a_file, &config.order, config.bounds, config.renamer.as_ref())?;
+        let mut window = Window::new(SortedReader::open(b_file, &config.order, config.bounds, config.renamer.as_ref())?)?;
+        let orientation = config.signed.unwrap_or(Orientation::Ref);
+        while let Some(a) = a_reader.try_next()? {
+            window.advance(&a.coords, config.k)?;
+            let candidates: Vec<(i64, &BgLine)> = window.candidates().filter_map(|b| {
+                let distance = signed_distance(&a, b, orientation);
+                let skip = (distance == 0 && config.ignore_overlaps)
+                           || (distance < 0 && config.ignore_upstream)
+                           || (distance > 0 && config.ignore_downstream);
+                if skip { None } else { Some((distance, b)) }
+            }).collect();
+            let chosen = choose(candidates, config);
+            let with_distance = config.report_distance || config.signed.is_some();
+            if chosen.is_empty() {
+                let missing = format!("{}\t.\t-1\t-1{}", a, "\t.".repeat(window.extra_cols));
+                if with_distance {
+                    write_line(output, &format!("{}\t-1", missing));
+                } else {
+                    write_line(output, &missing);
+                }
+            }
+            for (distance, b) in chosen {
+                match (config.signed, config.report_distance) {
+                    (Some(_), _) => write_line(output, &format!("{}\t{}\t{}", a, b, distance)),
+                    (None, true) => write_line(output, &format!("{}\t{}\t{}", a, b, distance.abs())),
+                    (None, false) => write_line(output, &format!("{}\t{}", a, b)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn closest_main(a_file: &str, b_file: &str, config: ClosestConfig) -> Result<(), String> {
+        run_to_stdout(|output| closest_bed(a_file, b_file, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_closest {
+        use super::*;
+        use std::rc::Rc;
+        use super::super::chrom_sizes::Genome;
+        use super::super::output::output_lines;
+
+        //the names of A and B, and the distance (if there is one) of each line
+        fn closest(config: ClosestConfig) -> Vec<String> {
+            let lines = output_lines(|output| closest_bed("test/closest/a.bed", "test/closest/b.bed", &config, output)).unwrap();
+            lines.iter().map(|line| {
+                let cols: Vec<&str> = line.split('\t').collect();
+                match cols.get(12) {
+                    Some(distance) => format!("{}-{}:{}", cols[3], cols[9], distance),
+                    None => format!("{}-{}", cols[3], cols[9]),
+                }
+            }).collect()
+        }
+
+        #[test]
+        fn closest_defaults() {
+            assert_eq!(closest(ClosestConfig::default()), vec!["a1-b2", "a2-b3", "a2-b4", "a3-b5", "a3-b6", "a4-b7", "a5-."]);
+            let config = ClosestConfig{report_distance: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2:0", "a2-b3:101", "a2-b4:101", "a3-b5:151", "a3-b6:151", "a4-b7:51", "a5-.:-1"]);
+            let lines = output_lines(|output| closest_bed("test/closest/a.bed", "test/closest/b.bed", &ClosestConfig::default(), output)).unwrap();
+            assert_eq!(lines.last().unwrap(), "chr3\t10\t20\ta5\t0\t+\t.\t-1\t-1\t.\t.\t.");
+        }
+
+        #[test]
+        fn closest_ties() {
+            let config = ClosestConfig{ties: Ties::First, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2", "a2-b3", "a3-b5", "a4-b7", "a5-."]);
+            let config = ClosestConfig{ties: Ties::Last, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2", "a2-b4", "a3-b6", "a4-b7", "a5-."]);
+        }
+
+        #[test]
+        fn closest_signed() {
+            let config = ClosestConfig{signed: Some(Orientation::Ref), ..ClosestConfig::default()};
+            assert_eq!(&closest(config)[1..3], ["a2-b3:-101", "a2-b4:101"]);
+            let config = ClosestConfig{signed: Some(Orientation::A), ..ClosestConfig::default()};
+            assert_eq!(&closest(config)[1..3], ["a2-b3:101", "a2-b4:-101"]);
+            let config = ClosestConfig{signed: Some(Orientation::B), ..ClosestConfig::default()};
+            assert_eq!(&closest(config)[1..3], ["a2-b3:101", "a2-b4:101"]);
+        }
+
+        #[test]
+        fn closest_ignore() {
+            let config = ClosestConfig{ignore_overlaps: true, report_distance: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config)[0], "a1-b1:51");
+            let config = ClosestConfig{signed: Some(Orientation::Ref), ignore_upstream: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2:0", "a2-b4:101", "a3-b5:151", "a3-b6:151", "a4-b7:51", "a5-.:-1"]);
+            let config = ClosestConfig{signed: Some(Orientation::Ref), ignore_downstream: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2:0", "a2-b3:-101", "a3-b4:-201", "a4-.:-1", "a5-.:-1"]);
+        }
+
+        #[test]
+        fn closest_k() {
+            let config = ClosestConfig{k: 2, report_distance: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2:0", "a1-b1:51", "a2-b3:101", "a2-b4:101", "a3-b5:151", "a3-b6:151", "a4-b7:51", "a5-.:-1"]);
+            let config = ClosestConfig{k: 3, report_distance: true, ..ClosestConfig::default()};
+            assert_eq!(closest(config), vec!["a1-b2:0", "a1-b1:51", "a1-b3:101",
+                                             "a2-b3:101", "a2-b4:101", "a2-b2:341",
+                                             "a3-b5:151", "a3-b6:151", "a3-b4:201",
+                                             "a4-b7:51", "a5-.:-1"]);
+        }
+
+        #[test]
+        fn closest_bounds() {
+            //a genome given for the order also bounds the intervals
+            let genome = Genome::from_file("test/sort/genome.chrom.sizes").unwrap();
+            let config = ClosestConfig{order: ChromOrder::Genome(Rc::new(genome)), ..ClosestConfig::default()};
+            let expect = String::from("Error in 'test/closest/b.bed', line 5: interval chr1:1250-1400 extends past the end of the chromosome (1000)");
+            if let Err(msg) = closest_bed("test/closest/a.bed", "test/closest/b.bed", &config, &mut Vec::new()) {
+                assert_eq!(msg, expect)
+            } else {
+                panic!("Expected Err from closest_bed(), received Ok(_) instead");
+            }
+        }
+
+        #[test]
+        fn closest_large_gap() {
+            //the intervals between two A intervals are pruned as they are read
+            let dir = std::env::temp_dir().join(format!("ironbed-test-closest-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let (a_file, b_file) = (dir.join("a.bed"), dir.join("b.bed"));
+            std::fs::write(&a_file, "chr1\t0\t10\ta1\nchr1\t1000010\t1000020\ta2\n").unwrap();
+            let b_lines: Vec<String> = (0..100_000).map(|i| format!("chr1\t{}\t{}\tb{}\n", 10 + i * 10, 15 + i * 10, i)).collect();
+            std::fs::write(&b_file, b_lines.concat()).unwrap();
+            let (a_file, b_file) = (a_file.to_str().unwrap(), b_file.to_str().unwrap());
+
+            let order = ChromOrder::Lexicographic;
+            let mut window = Window::new(SortedReader::open(b_file, &order, OutOfBounds::Reject, None).unwrap()).unwrap();
+            window.advance(&ChromSeg{chrom: "chr1".to_string(), start: 1000010, stop: 1000020}, 2).unwrap();
+            assert_eq!(window.upstream.len(), 2);
+            assert!(window.upstream_limit <= 4);
+
+            let mut output = Vec::new();
+            let config = ClosestConfig{report_distance: true, ..ClosestConfig::default()};
+            closest_bed(a_file, b_file, &config, &mut output).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), "chr1\t0\t10\ta1\tchr1\t10\t15\tb0\t1\n\
+                                                           chr1\t1000010\t1000020\ta2\tchr1\t1000000\t1000005\tb99999\t6\n");
+        }
+    }
+}
+
+pub mod subtract {
+    use std::io::Write;
+    use super::chrom_geo::ChromSeg;
+    use super::bedgraph::{BgIterator, BgLine};
+    use super::intersect::read_index;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone)]
+    pub struct SubtractConfig {
+        //-A: remove the whole A interval if it overlaps B at all
+        pub whole: bool,
+        //-f: only subtract B intervals that overlap at least this fraction of A
+        pub min_frac: f64,
+        //renames the chromosomes of both files
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for SubtractConfig {
+        fn default() -> SubtractConfig {
+            SubtractConfig{whole: false, min_frac: 0.0, renamer: None}
+        }
+    }
+
+    //the parts of a that are not covered by any of the intervals in b
+    //(which are sorted by start)
+    fn pieces(a: &ChromSeg, b: &[&ChromSeg]) -> Vec<ChromSeg> {
+        let mut pieces = Vec::new();
+        let mut start = a.start;
+        for seg in b {
+            if seg.start > start {
+                pieces.push(ChromSeg{chrom: a.chrom.clone(), start, stop: seg.start.min(a.stop)});
+            }
+            start = start.max(seg.stop);
+        }
+        if start < a.stop {
+            pieces.push(ChromSeg{chrom: a.chrom.clone(), start, stop: a.stop});
+        }
+        pieces
+    }
+
+    //remove the parts of the intervals in a_file that overlap b_file,
+    //keeping the extra columns of a_file
+    //b_file is read into memory, and neither file needs to be sorted
+    pub fn subtract_bed<W: Write>(a_file: &str, b_file: &str, config: &SubtractConfig, output: &mut W) -> Result<(), String> {
+        let index = read_index(b_file, config.renamer.as_ref())?;
+        let mut a_reader = BgIterator::open(a_file, config.renamer.as_ref())?;
+        while let Some(a) = a_reader.try_next()? {
+            let length = (a.coords.stop - a.coords.start) as f64;
+            let overlaps: Vec<&ChromSeg> = index.get(&a.coords.chrom).into_iter()
+                                                .flat_map(|chrom| chrom.overlapping(&a.coords))
+                                                .map(|b| &b.coords)
+                                                .filter(|b| {
+                                                    let bases = a.coords.stop.min(b.stop) - a.coords.start.max(b.start);
+                                                    bases as f64 >= config.min_frac * length
+                                                })
+                                                .collect();
+            if overlaps.is_empty() {
+                write_line(output, &a);
+            } else if !config.whole {
+                for coords in pieces(&a.coords, &overlaps) {
+                    write_line(output, &BgLine{coords, data: a.data.clone()});
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn subtract_main(a_file: &str, b_file: &str, config: SubtractConfig) -> Result<(), String> {
+        run_to_stdout(|output| subtract_bed(a_file, b_file, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_subtract {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn subtract(config: SubtractConfig) -> Vec<String> {
+            output_lines(|output| subtract_bed("test/subtract/a.bed", "test/subtract/b.bed", &config, output)).unwrap()
+        }
+
+        #[test]
+        fn subtract_pieces() {
+            assert_eq!(subtract(SubtractConfig::default()), vec!["chr1\t100\t150\tp1\t5\t+", "chr1\t160\t180\tp1\t5\t+",
+                                                               "chr1\t320\t350\tp2\t7\t-", "chr1\t360\t400\tp2\t7\t-",
+                                                               "chr1\t500\t540\tp3\t1\t+", "chr1\t590\t600\tp3\t1\t+",
+                                                               "chr2\t100\t200\tp4\t3\t+"]);
+        }
+
+        #[test]
+        fn subtract_whole() {
+            let config = SubtractConfig{whole: true, ..SubtractConfig::default()};
+            assert_eq!(subtract(config), vec!["chr2\t100\t200\tp4\t3\t+"]);
+        }
+
+        #[test]
+        fn subtract_fraction() {
+            let config = SubtractConfig{min_frac: 0.5, ..SubtractConfig::default()};
+            assert_eq!(subtract(config), vec!["chr1\t100\t200\tp1\t5\t+", "chr1\t300\t400\tp2\t7\t-",
+                                              "chr1\t500\t540\tp3\t1\t+", "chr1\t590\t600\tp3\t1\t+",
+                                              "chr2\t100\t200\tp4\t3\t+"]);
+            let config = SubtractConfig{whole: true, min_frac: 0.5, ..SubtractConfig::default()};
+            assert_eq!(subtract(config), vec!["chr1\t100\t200\tp1\t5\t+", "chr1\t300\t400\tp2\t7\t-",
+                                              "chr2\t100\t200\tp4\t3\t+"]);
+        }
+    }
+}
+
+pub mod coverage {
+    use std::collections::BTreeMap;
+    use std::io::Write;
+    use super::chrom_geo::Coord;
+    use super::genomecov::{depth_runs, write_histogram};
+    use super::intersect::Window;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use super::chrom_sizes::{ChromOrder, OutOfBounds};
+    use super::sort::SortedReader;
+    use std::rc::Rc;
+
+    //what to write after each interval in A
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CoverageOutput {
+        //the number of B intervals, bases covered, length and fraction covered
+        Counts,
+        //-hist: the number of bases at each depth, then the same for all of A
+        Histogram,
+        //-d: the depth of every base (1-based, from the start of A)
+        PerBase,
+        //-mean: the mean depth
+        Mean,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct CoverageConfig {
+        pub output: CoverageOutput,
+        //the order both inputs are sorted in
+        pub order: ChromOrder,
+        //for the intervals outside the genome, if the order is a genome's
+        pub bounds: OutOfBounds,
+        //renames the chromosomes of both inputs
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for CoverageConfig {
+        fn default() -> CoverageConfig {
+            CoverageConfig{output: CoverageOutput::Counts, order: ChromOrder::Lexicographic, bounds: OutOfBounds::Reject, renamer: None}
+        }
+    }
+
+    //report how much of each interval in a_file is covered by the intervals
+    //in b_file, in a sweep over both files (which must be sorted by chrom,
+    //then start), so only the B intervals overlapping A are held in memory
+    pub fn coverage_bed<W: Write>(a_file: &str, b_file: &str, config: &CoverageConfig, output: &mut W) -> Result<(), String> {
+        let mut a_reader = SortedReader::open(a_file, &config.order, config.bounds, config.renamer.as_ref())?;
+        let mut window = Window::new(SortedReader::open(b_file, &config.order, config.bounds, config.renamer.as_ref())?)?;
+        let mut all_hist: BTreeMap<i64, Coord> = BTreeMap::new();
+        let mut all_length = 0;
+        while let Some(a) = a_reader.try_next()? {
+            window.advance(&a.coords)?;
+            let (start, stop) = (a.coords.start, a.coords.stop);
+            let length = stop.saturating_sub(start);
+            //the depth changes, relative to the start of A
+            let mut events: Vec<(Coord, i64)> = Vec::new();
+            let mut count = 0;
+            for b in window.lines.iter().filter(|b| b.coords.start < stop) {
+                count += 1;
+                events.push((b.coords.start.max(start) - start, 1));
+                events.push((b.coords.stop.min(stop) - start, -1));
+            }
+            let runs = depth_runs(&mut events, length);
+            match config.output {
+                CoverageOutput::Counts => {
+                    let covered: Coord = runs.iter().filter(|run| run.2 > 0).map(|run| run.1 - run.0).sum();
+                    let frac = if length > 0 { covered as f64 / length as f64 } else { 0.0 };
+                    write_line(output, &format!("{}\t{}\t{}\t{}\t{}", a, count, covered, length, frac));
+                },
+                CoverageOutput::Histogram => {
+                    let mut histogram: BTreeMap<i64, Coord> = BTreeMap::new();
+                    for (run_start, run_stop, depth) in runs {
+                        *histogram.entry(depth).or_insert(0) += run_stop - run_start;
+                        *all_hist.entry(depth).or_insert(0) += run_stop - run_start;
+                    }
+                    all_length += length;
+                    write_histogram(output, &a.to_string(), &histogram, length);
+                },
+                CoverageOutput::PerBase => {
+                    for (run_start, run_stop, depth) in runs {
+                        for pos in run_start..run_stop {
+                            write_line(output, &format!("{}\t{}\t{}", a, pos + 1, depth));
+                        }
+                    }
+                },
+                CoverageOutput::Mean => {
+                    let bases: i64 = runs.iter().map(|run| (run.1 - run.0) as i64 * run.2).sum();
+                    let mean = if length > 0 { bases as f64 / length as f64 } else { 0.0 };
+                    write_line(output, &format!("{}\t{}", a, mean));
+                },
+            }
+        }
+        if config.output == CoverageOutput::Histogram {
+            write_histogram(output, "all", &all_hist, all_length);
+        }
+        Ok(())
+    }
+
+    pub fn coverage_main(a_file: &str, b_file: &str, config: CoverageConfig) -> Result<(), String> {
+        run_to_stdout(|output| coverage_bed(a_file, b_file, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_coverage {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn coverage(output: CoverageOutput) -> Vec<String> {
+            let config = CoverageConfig{output, ..CoverageConfig::default()};
+            output_lines(|output| coverage_bed("test/coverage/a.bed", "test/coverage/b.bed", &config, output)).unwrap()
+        }
+
+        #[test]
+        fn coverage_counts() {
+            assert_eq!(coverage(CoverageOutput::Counts), vec!["chr1\t0\t100\tr1\t0\t+\t3\t55\t100\t0.55",
+                                                              "chr1\t50\t60\tr2\t0\t+\t1\t5\t10\t0.5",
+                                                              "chr2\t0\t10\tr3\t0\t-\t0\t0\t10\t0"]);
+            assert_eq!(coverage(CoverageOutput::Mean), vec!["chr1\t0\t100\tr1\t0\t+\t0.65",
+                                                            "chr1\t50\t60\tr2\t0\t+\t0.5",
+                                                            "chr2\t0\t10\tr3\t0\t-\t0"]);
+        }
+
+        #[test]
+        fn coverage_histogram() {
+            assert_eq!(coverage(CoverageOutput::Histogram), vec!["chr1\t0\t100\tr1\t0\t+\t0\t45\t100\t0.45",
+                                                                 "chr1\t0\t100\tr1\t0\t+\t1\t45\t100\t0.45",
+                                                                 "chr1\t0\t100\tr1\t0\t+\t2\t10\t100\t0.1",
+                                                                 "chr1\t50\t60\tr2\t0\t+\t0\t5\t10\t0.5",
+                                                                 "chr1\t50\t60\tr2\t0\t+\t1\t5\t10\t0.5",
+                                                                 "chr2\t0\t10\tr3\t0\t-\t0\t10\t10\t1",
+                                                                 "all\t0\t60\t120\t0.5",
+                                                                 "all\t1\t50\t120\t0.4166666666666667",
+                                                                 "all\t2\t10\t120\t0.08333333333333333"]);
+        }
+
+        #[test]
+        fn coverage_per_base() {
+            let lines = coverage(CoverageOutput::PerBase);
+            assert_eq!(lines.len(), 120);
+            assert_eq!(lines[10], "chr1\t0\t100\tr1\t0\t+\t11\t1");
+            assert_eq!(lines[25], "chr1\t0\t100\tr1\t0\t+\t26\t2");
+            assert_eq!(lines[104], "chr1\t50\t60\tr2\t0\t+\t5\t1");
+            assert_eq!(lines[105], "chr1\t50\t60\tr2\t0\t+\t6\t0");
+        }
+
         #[test]
-        fn min_iterators() {
-            let bedgraph1 = BgIterator::new("test/unionbedg/1.bg").unwrap();
-            let bedgraph2 = BgIterator::new("test/unionbedg/2.bg").unwrap();
-            let bedgraph3 = BgIterator::new("test/unionbedg/3.bg").unwrap();
-            let mut readers: Vec<BgIterator> = vec![bedgraph1, bedgraph2, bedgraph3];
-            let lines: Vec<BgLine> = readers.iter_mut().map(|x| x.next().unwrap()).collect();
-            let min_start: chrom_geo::ChromPos = lines.iter().map(|x| x.coords.start_pos()).min().unwrap();
-            assert_eq!(min_start, chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 900});
-            let min_start: chrom_geo::ChromPos = lines.iter().map(|x| x.coords.stop_pos()).min().unwrap();
-            assert_eq!(min_start, chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 1500});
+        fn coverage_unsorted() {
+            let mut output = Vec::new();
+            if let Err(msg) = coverage_bed("test/intersect/b.unsorted.bed", "test/coverage/b.bed", &CoverageConfig::default(), &mut output) {
+                assert!(msg.starts_with("Error: Sorted input specified, but the file test/intersect/b.unsorted.bed has the following out of order record"));
+            } else {
+                panic!("Expected Err from coverage_bed(), received Ok(_) instead");
+            }
         }
     }
 }
 
-pub mod union {
-    use super::chrom_geo;
-    use super::bedgraph::{BgIterator, BgLine};
-    use super::chrom_sizes::{chromsizes_to_map, ChromSizes};
+pub mod map {
+    use std::io::Write;
+    use super::chrom_geo::Coord;
+    use super::bedgraph::BgLine;
+    use super::intersect::Window;
+    use super::merge::pair_columns;
+    use super::chrom_alias::Renamer;
+    use super::output::{run_to_stdout, write_line};
+    use super::chrom_sizes::{ChromOrder, OutOfBounds};
+    use super::sort::SortedReader;
+    use std::rc::Rc;
 
-    //Each reader can have three states:
-    // In - the current position of the Union interesects with the Reader at BgLine
-    // Out - the current position of the Union does NOT intersect with the Reader at BgLine
-    // Done - the reader has nothing left
-    enum UnionLine {
-        In(BgLine),
-        Out(BgLine),
-        Done
+    //an operation applied to a column of the B intervals overlapping an
+    //interval in A (-o)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MapOp {
+        Sum,
+        Mean,
+        //the mean weighted by the number of bases each B interval overlaps A
+        WMean,
+        Median,
+        Min,
+        Max,
+        Count,
     }
 
-    #[derive(Debug)]
-    pub struct UnionConfig<'a> {
-        pub report_empty: bool,
-        pub filler: &'a str,
-        pub genome: Option<ChromSizes>,
+    impl MapOp {
+        pub fn parse(op: &str) -> Result<MapOp, String> {
+            match op {
+                "sum" => Ok(MapOp::Sum),
+                "mean" => Ok(MapOp::Mean),
+                "wmean" => Ok(MapOp::WMean),
+                "median" => Ok(MapOp::Median),
+                "min" => Ok(MapOp::Min),
+                "max" => Ok(MapOp::Max),
+                "count" => Ok(MapOp::Count),
+                _ => Err(format!("***** ERROR: Invalid operation \"{}\". Expected one of sum, mean, wmean, median, min, max, count", op)),
+            }
+        }
+
+        //apply the operation to the numeric values of a column, with the
+        //number of bases of A each one covers (None if there are none)
+        fn apply(self, values: &[(f64, Coord)]) -> Option<f64> {
+            if values.is_empty() {
+                return None;
+            }
+            let numbers = || values.iter().map(|value| value.0);
+            match self {
+                MapOp::Sum => Some(numbers().sum()),
+                MapOp::Mean => Some(numbers().sum::<f64>() / values.len() as f64),
+                MapOp::WMean => {
+                    let bases: Coord = values.iter().map(|value| value.1).sum();
+                    if bases == 0 {
+                        return None;
+                    }
+                    Some(values.iter().map(|value| value.0 * value.1 as f64).sum::<f64>() / bases as f64)
+                },
+                MapOp::Median => {
+                    let mut sorted: Vec<f64> = numbers().collect();
+                    //map_bed() leaves out NaN (and the infinities), so every value has an order
+                    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    //the middle value, or the mean of the middle two
+                    let (low, high) = (sorted[(sorted.len() - 1) / 2], sorted[sorted.len() / 2]);
+                    Some(low + (high - low) / 2.0)
+                },
+                MapOp::Min => Some(numbers().fold(f64::INFINITY, f64::min)),
+                MapOp::Max => Some(numbers().fold(f64::NEG_INFINITY, f64::max)),
+                MapOp::Count => Some(values.len() as f64),
+            }
+        }
     }
 
-    pub struct BgUnion<'a> {
-        readers: Vec<BgIterator>,
-        lines: Vec<UnionLine>,
-        curr: chrom_geo::ChromPos,
-        config: UnionConfig<'a>,
+    //pair up the columns (-c) and operations (-o), as for merge
+    pub fn parse_columns(columns: &str, ops: &str) -> Result<Vec<(usize, MapOp)>, String> {
+        pair_columns(columns, ops, MapOp::parse)
     }
 
-    impl<'a> BgUnion<'a> {
-        pub fn new(readers: Vec<BgIterator>) -> Result<BgUnion<'static>, &'static str> {
-            // simply call the "with_config" method using the default config below
-            let config = UnionConfig{report_empty: false, filler: "0", genome: None};
-            BgUnion::with_config(readers, config)
+    #[derive(Debug, Clone)]
+    pub struct MapConfig {
+        pub columns: Vec<(usize, MapOp)>,
+        //written for the operations (other than count) on an interval in A
+        //with no values
+        pub null: String,
+        //the order both inputs are sorted in
+        pub order: ChromOrder,
+        //for the intervals outside the genome, if the order is a genome's
+        pub bounds: OutOfBounds,
+        //renames the chromosomes of both inputs
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl Default for MapConfig {
+        fn default() -> MapConfig {
+            MapConfig{columns: vec![(5, MapOp::Sum)], null: ".".to_string(), order: ChromOrder::Lexicographic, bounds: OutOfBounds::Reject, renamer: None}
         }
-        
-        pub fn with_config(mut readers: Vec<BgIterator>, config: UnionConfig) -> Result<BgUnion, &'static str> {
-            let mut lines: Vec<UnionLine> = Vec::with_capacity(readers.len());
-            for rdr in readers.iter_mut() {
-                match rdr.next() {
-                    Some(bgl) => lines.push(UnionLine::Out(bgl)),
-                    None => lines.push(UnionLine::Done),
+    }
+
+    //the value of a column (numbered from 1) of a line
+    fn column(line: &BgLine, col: usize) -> Option<String> {
+        match col {
+            1 => Some(line.coords.chrom.clone()),
+            2 => Some(line.coords.start.to_string()),
+            3 => Some(line.coords.stop.to_string()),
+            _ => line.data.as_ref().and_then(|data| data.split('\t').nth(col - 4)).map(|value| value.to_string()),
+        }
+    }
+
+    //summarize the values of the intervals in b_file (such as the signal
+    //of a bedGraph) over each interval in a_file, in a sweep over both files
+    //(which must be sorted by chrom, then start)
+    pub fn map_bed<W: Write>(a_file: &str, b_file: &str, config: &MapConfig, output: &mut W) -> Result<(), String> {
+        let mut a_reader = SortedReader::open(a_file, &config.order, config.bounds, config.renamer.as_ref())?;
+        let mut window = Window::new(SortedReader::open(b_file, &config.order, config.bounds, config.renamer.as_ref())?)?;
+        while let Some(a) = a_reader.try_next()? {
+            window.advance(&a.coords)?;
+            let hits: Vec<&BgLine> = window.lines.iter().filter(|b| b.coords.start < a.coords.stop).collect();
+            let mut line = a.to_string();
+            for (col, op) in &config.columns {
+                let mut values: Vec<(f64, Coord)> = Vec::with_capacity(hits.len());
+                for b in &hits {
+                    let value = match column(b, *col) {
+                        Some(value) => value,
+                        None => {
+                            let fields = b.data.as_ref().map_or(0, |data| data.split('\t').count());
+                            return Err(format!("***** ERROR: Requested column {}, but database file {} only has fields 1 - {}.", col, b_file, fields + 3));
+                        },
+                    };
+                    let bases = a.coords.stop.min(b.coords.stop) - a.coords.start.max(b.coords.start);
+                    match value.parse::<f64>() {
+                        //'nan' and 'inf' parse, but would make every result the same
+                        Ok(number) if number.is_finite() => values.push((number, bases)),
+                        //count doesn't need numbers
+                        _ if *op == MapOp::Count => values.push((0.0, bases)),
+                        _ => eprintln!("***** WARNING: Non numeric value {} in {}.", value, col),
+                    }
+                }
+                line.push('\t');
+                match op.apply(&values) {
+                    Some(result) => line.push_str(&result.to_string()),
+                    None if *op == MapOp::Count => line.push('0'),
+                    None => line.push_str(&config.null),
                 }
             }
-            //TODO: add logic to find the first site
-            //remove this hard coding
-            let curr = chrom_geo::ChromPos{chrom: "chr1".to_string(), index: 0};
-            Ok( BgUnion{readers, lines, curr, config} )
+            write_line(output, &line);
         }
+        Ok(())
+    }
 
-        fn next_transition(&self) -> chrom_geo::ChromPos {
-            //TODO: add logic for empty spaces / provided sizes file
-            self.lines.iter().filter_map(
-                | x | match x {
-                        UnionLine::Done => None,
-                        UnionLine::In(ref line) => Some(line.coords.stop_pos()),
-                        UnionLine::Out(ref line) => Some(line.coords.start_pos()),
-            }).min().unwrap()
+    pub fn map_main(a_file: &str, b_file: &str, config: MapConfig) -> Result<(), String> {
+        run_to_stdout(|output| map_bed(a_file, b_file, &config, output))
+    }
+
+    #[cfg(test)]
+    mod test_map {
+        use super::*;
+        use super::super::output::output_lines;
+
+        fn mapped(config: &MapConfig) -> Result<Vec<String>, String> {
+            output_lines(|output| map_bed("test/map/regions.bed", "test/map/signal.bg", config, output))
         }
 
-        fn advance_lines(&mut self, curr: &chrom_geo::ChromPos) {
-            self.lines = std::mem::replace(&mut self.lines, vec![]).into_iter().zip(self.readers.iter_mut()).map(| (old_line, reader) | {
-                match old_line {
-                    UnionLine::Done => UnionLine::Done,
-                    UnionLine::Out(line_data) => {
-                        // here we assume that if a line is "outside"
-                        // then we either haven't reached it yet, or have, and it is in
-                        if line_data.starts_after(curr) {
-                            UnionLine::Out(line_data)
-                        } else {
-                            // line has been reached!
-                            UnionLine::In(line_data)
-                        }
-                    },
-                    UnionLine::In(line_data) => {
-                        // here we assume that the line has been reached
-                        // thus we will either remain inside it, or move on and get a new line
-                        if line_data.ends_before(curr) {
-                            match reader.next() {
-                                Some(new_line) => {
-                                    if new_line.starts_after(curr) {
-                                        UnionLine::Out(new_line)
-                                    } else {
-                                    // line has been reached!
-                                        UnionLine::In(new_line)
-                                    }
-                                },
-                                //reader has nothing left
-                                None => UnionLine::Done,
-                            }
-                        } else {
-                            UnionLine::In(line_data)
-                        }
-                    },
-                }
-            }).collect();
+        #[test]
+        fn map_operations() {
+            let config = MapConfig{columns: parse_columns("4", "mean,max,sum,count,median,wmean").unwrap(), ..MapConfig::default()};
+            assert_eq!(mapped(&config).unwrap(), vec!["chr1\t0\t20\tg0\t0\t+\t2\t3\t4\t2\t2\t2",
+                                                      "chr1\t5\t25\tg1\t0\t+\t2\t3\t6\t3\t2\t2.25",
+                                                      "chr1\t40\t50\tg2\t0\t+\t.\t.\t.\t0\t.\t.",
+                                                      "chr1\t45\t70\tg3\t0\t-\t4\t4\t4\t1\t4\t4",
+                                                      "chr2\t10\t20\tg4\t0\t+\t5\t5\t5\t1\t5\t5",
+                                                      "chr3\t0\t10\tg5\t0\t+\t.\t.\t.\t0\t.\t."]);
+            let config = MapConfig{columns: parse_columns("4", "min,count").unwrap(), null: "NA".to_string(), ..MapConfig::default()};
+            let lines = mapped(&config).unwrap();
+            assert_eq!(lines[1], "chr1\t5\t25\tg1\t0\t+\t1\t3");
+            assert_eq!(lines[2], "chr1\t40\t50\tg2\t0\t+\tNA\t0");
         }
-    }
 
-    impl<'a> Iterator for BgUnion<'a> {
-        type Item = BgLine;
+        #[test]
+        fn map_nan() {
+            let config = MapConfig{columns: parse_columns("4", "median,count").unwrap(), ..MapConfig::default()};
+            let mut output = Vec::new();
+            map_bed("test/map/regions.bed", "test/map/nan.bg", &config, &mut output).unwrap();
+            let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+            assert_eq!(lines[0], "chr1\t0\t20\tg0\t0\t+\t1\t2");
+            assert_eq!(lines[1], "chr1\t5\t25\tg1\t0\t+\t1\t3");
+            assert_eq!(lines[2], "chr1\t40\t50\tg2\t0\t+\t.\t0");
+        }
 
-        fn next(&mut self) -> Option<Self::Item> {
-            //make this a flag that gets updated automatically
-            if self.lines.iter().all(| x | match x { UnionLine::Done => true, _ => false}) {
-                return None;
-            }
-            let next_trans = self.next_transition();
-            let has_in = self.lines.iter().any(| x | match x { UnionLine::In(_) => true, _ => false});
-            //prep the data... do this in a better way if possible
-            let formatted_data: String = self.lines.iter().map(| x | {
-                match x { 
-                    UnionLine::In(ref line) => {
-                        match line.data {
-                            Some(ref line) => line,
-                            None => self.config.filler,
-                        }
-                    },
-                    _ => self.config.filler,
-                }}).collect::<Vec<&str>>().join("\t");
-            //advance all the readers / lines based on the next transition
-            self.advance_lines(&next_trans);
-            //swap "curr" with the new transition
-            //next_trans is now the 
-            let old_trans = std::mem::replace(&mut self.curr, next_trans);
-            let coords = chrom_geo::ChromSeg{
-                    chrom: old_trans.chrom,
-                    start: old_trans.index,
-                    stop: self.curr.index
-            };
-            let line = BgLine{coords, data: Some(formatted_data)};
-            // check there are any bedgraph data for this region
-            if has_in {
-                //yield the line
-                Some(line)
+        #[test]
+        fn map_parse_columns() {
+            assert_eq!(parse_columns("4", "wmean,median"), Ok(vec![(4, MapOp::WMean), (4, MapOp::Median)]));
+            assert!(parse_columns("4", "collapse").is_err());
+        }
+
+        #[test]
+        fn map_errors() {
+            let expect = String::from("***** ERROR: Requested column 5, but database file test/map/signal.bg only has fields 1 - 4.");
+            if let Err(msg) = mapped(&MapConfig::default()) {
+                assert_eq!(msg, expect)
             } else {
-                // otherwise, check if we should yield this
-                // line or not, depending on the settings
-                if self.config.report_empty {
-                    Some(line)
-                } else {
-                   self.next() 
-                }
+                panic!("Expected Err from map_bed(), received Ok(_) instead");
             }
         }
     }
+}
 
+pub mod makewindows {
+    use std::io::Write;
+    use super::chrom_geo::{ChromSeg, Coord};
+    use super::bedgraph::BgIterator;
+    use super::chrom_alias::Renamer;
+    use super::chrom_sizes::Genome;
+    use super::output::{run_to_stdout, write_line};
+    use std::rc::Rc;
 
-    pub fn union_main(filenames: Vec<&str>, filler: &str, report_empty: bool, genome_file: Option<&str>) -> Result<(), String> {
-        // open the bedgraph files
-        let mut bg_iters: Vec<BgIterator> = Vec::with_capacity(filenames.len());
-        for fname in filenames {
-            match BgIterator::new(fname) {
-                Ok(bg_iter) => bg_iters.push(bg_iter),
-                Err(e) => return Err(format!("{}: '{}'", e, fname)),
-            }
+    //how to split each chromosome or region
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Windows {
+        //-w and -s: windows of this size, starting every step bases (the
+        //last window is cut at the end of the region)
+        Size{size: Coord, step: Coord},
+        //-n: this many windows of (almost) equal size
+        Count(Coord),
+    }
+
+    //the name written after each window (-i)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum WindowIds {
+        None,
+        //the number of the window in its region, from 1
+        WinNum,
+        //the name of the region (column 4, or the chromosome), '_' and the
+        //number of the window
+        SrcWinNum,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct WindowConfig {
+        pub windows: Windows,
+        pub ids: WindowIds,
+        //renames the chromosomes of a BED file given with -b
+        pub renamer: Option<Rc<Renamer>>,
+    }
+
+    impl WindowConfig {
+        pub fn new(windows: Windows) -> WindowConfig {
+            WindowConfig{windows, ids: WindowIds::None, renamer: None}
         }
-        //prepare the config
-        let genome = match genome_file {
-            None => None,
-            Some(fname) => Some(chromsizes_to_map(fname)?),
-        };
-        let config = UnionConfig{filler, report_empty, genome};
-        let union = BgUnion::with_config(bg_iters, config)?;
-        for line in union {
-            println!("{}", line);
+    }
+
+    //what to split into windows
+    #[derive(Debug, Clone, Copy)]
+    pub enum WindowSource<'a> {
+        //-g: every chromosome, in the order of the genome file
+        Genome(&'a Genome),
+        //-b: the intervals of a BED file
+        Bed(&'a str),
+    }
+
+    fn write_window<W: Write>(output: &mut W, window: &ChromSeg, source: &str, num: usize, ids: WindowIds) {
+        match ids {
+            WindowIds::None => write_line(output, window),
+            WindowIds::WinNum => write_line(output, &format!("{}\t{}", window, num)),
+            WindowIds::SrcWinNum => write_line(output, &format!("{}\t{}_{}", window, source, num)),
+        }
+    }
+
+    //false if the region is too short for the windows, and is skipped
+    fn split_region<W: Write>(output: &mut W, region: &ChromSeg, source: &str, config: &WindowConfig) -> bool {
+        let length = region.stop.saturating_sub(region.start);
+        let window = |start, stop| ChromSeg{chrom: region.chrom.clone(), start, stop};
+        match config.windows {
+            Windows::Size{size, step} => {
+                let mut start = region.start;
+                let mut num = 1;
+                while start < region.stop {
+                    let stop = region.stop.min(start + size);
+                    write_window(output, &window(start, stop), source, num, config.ids);
+                    if stop == region.stop {
+                        break;
+                    }
+                    start += step;
+                    num += 1;
+                }
+            },
+            //regions shorter than the number of windows are skipped, as
+            //some of their windows would be empty
+            Windows::Count(count) if length < count => return false,
+            Windows::Count(count) => {
+                for i in 0..count {
+                    let start = region.start + length * i / count;
+                    let stop = region.start + length * (i + 1) / count;
+                    write_window(output, &window(start, stop), source, i as usize + 1, config.ids);
+                }
+            },
+        }
+        true
+    }
+
+    //split the chromosomes of a genome or the intervals of a BED file into windows
+    //the regions that are too short for the windows are counted in a warning
+    pub fn make_windows<W: Write>(source: WindowSource, config: &WindowConfig, output: &mut W) -> Result<(), String> {
+        let mut skipped = 0;
+        match source {
+            WindowSource::Genome(genome) => {
+                for id in 0..genome.len() {
+                    let region = ChromSeg{chrom: genome.name(id).to_string(), start: 0, stop: genome.size(id)};
+                    if !split_region(output, &region, &region.chrom, config) {
+                        skipped += 1;
+                    }
+                }
+            },
+            WindowSource::Bed(filename) => {
+                let mut bg = BgIterator::open(filename, config.renamer.as_ref())?;
+                while let Some(line) = bg.try_next()? {
+                    let name = line.data.as_ref().and_then(|data| data.split('\t').next()).unwrap_or(&line.coords.chrom);
+                    if !split_region(output, &line.coords, name, config) {
+                        skipped += 1;
+                    }
+                }
+            },
+        }
+        if let Windows::Count(count) = config.windows {
+            if skipped > 0 {
+                eprintln!("Warning: skipped {} regions shorter than {} bp, which cannot be split into {} windows", skipped, count, count);
+            }
         }
         Ok(())
     }
 
-    
+    pub fn makewindows_main(source: WindowSource, config: WindowConfig) -> Result<(), String> {
+        run_to_stdout(|output| make_windows(source, &config, output))
+    }
+
     #[cfg(test)]
-    mod test_union {
+    mod test_makewindows {
         use super::*;
+        use super::super::output::output_lines;
 
-        #[test]
-        fn union_defaults() {
-            //gather the correct inputs into a union
-            let inputs: Vec<BgIterator> = vec!["test/unionbedg/1.bg", 
-                                               "test/unionbedg/2.bg",
-                                                "test/unionbedg/3.bg"].iter()
-                                                                      .map(|name| BgIterator::new(name).unwrap())
-                                                                      .collect();
-            let union = BgUnion::new(inputs).unwrap();
-            let expected_iterator = BgIterator::new("test/unionbedg/1+2+3.bg").unwrap();
-            for (actual, expected) in union.zip(expected_iterator) {
-                assert_eq!(actual, expected);
-            }
+        fn windows(source: WindowSource, config: WindowConfig) -> Vec<String> {
+            output_lines(|output| make_windows(source, &config, output)).unwrap()
         }
-        
+
         #[test]
-        fn union_defaults2() {
-            //gather the correct inputs into a union
-            let inputs: Vec<BgIterator> = vec!["test/unionbedg/empty-1.bg",
-                                               "test/unionbedg/empty-2.bg"].iter()
-                                                            .map(|name| BgIterator::new(name).unwrap())
-                                                            .collect();
-            let union = BgUnion::new(inputs).unwrap();
-            let expected_iterator = BgIterator::new("test/unionbedg/empty-1+2.bg").unwrap();
-            for (actual, expected) in union.zip(expected_iterator) {
-                assert_eq!(actual, expected);
-            }
+        fn makewindows_genome() {
+            let genome = Genome::from_file("test/makewindows/genome.chrom.sizes").unwrap();
+            let config = WindowConfig::new(Windows::Size{size: 1000, step: 1000});
+            assert_eq!(windows(WindowSource::Genome(&genome), config), vec!["chrB\t0\t1000", "chrB\t1000\t2000", "chrB\t2000\t2200", "chrA\t0\t700"]);
+            let config = WindowConfig{windows: Windows::Size{size: 1000, step: 500}, ids: WindowIds::WinNum, renamer: None};
+            assert_eq!(windows(WindowSource::Genome(&genome), config), vec!["chrB\t0\t1000\t1",
+                                                                            "chrB\t500\t1500\t2",
+                                                                            "chrB\t1000\t2000\t3",
+                                                                            "chrB\t1500\t2200\t4",
+                                                                            "chrA\t0\t700\t1"]);
         }
 
         #[test]
-        fn union_filler1() {
-            let inputs: Vec<BgIterator> = vec!["test/unionbedg/1.bg", 
-                                               "test/unionbedg/2.bg",
-                                                "test/unionbedg/3.bg"].iter()
-                                                                      .map(|name| BgIterator::new(name).unwrap())
-                                                                      .collect();
-            let config = UnionConfig{report_empty: false, filler: "NA", genome: None};
-            let union = BgUnion::with_config(inputs, config).unwrap();
-            let expected_iterator = BgIterator::new("test/unionbedg/1+2+3.NA-filling.bg").unwrap();
-            for (actual, expected) in union.zip(expected_iterator) {
-                assert_eq!(actual, expected);
-            }
+        fn makewindows_bed() {
+            let config = WindowConfig{windows: Windows::Count(3), ids: WindowIds::SrcWinNum, renamer: None};
+            assert_eq!(windows(WindowSource::Bed("test/makewindows/regions.bed"), config), vec!["chr1\t100\t136\tgeneX_1",
+                                                                                                "chr1\t136\t173\tgeneX_2",
+                                                                                                "chr1\t173\t210\tgeneX_3",
+                                                                                                "chr2\t0\t10\tchr2_1",
+                                                                                                "chr2\t10\t20\tchr2_2",
+                                                                                                "chr2\t20\t30\tchr2_3"]);
+            let config = WindowConfig::new(Windows::Size{size: 50, step: 50});
+            assert_eq!(windows(WindowSource::Bed("test/makewindows/regions.bed"), config), vec!["chr1\t100\t150",
+                                                                                                "chr1\t150\t200",
+                                                                                                "chr1\t200\t210",
+                                                                                                "chr1\t500\t502",
+                                                                                                "chr2\t0\t30"]);
         }
-        
+
         #[test]
-        fn union_filler2() {
-            //gather the correct inputs into a union
-            let inputs: Vec<BgIterator> = vec!["test/unionbedg/empty-1.bg",
-                                               "test/unionbedg/empty-2.bg"].iter()
-                                                            .map(|name| BgIterator::new(name).unwrap())
-                                                            .collect();
-            let config = UnionConfig{report_empty: false, filler: "apple", genome: None};
-            let union = BgUnion::with_config(inputs, config).unwrap();
-            let expected_iterator = BgIterator::new("test/unionbedg/empty-1+2.apple-filling.bg").unwrap();
-            for (actual, expected) in union.zip(expected_iterator) {
-                assert_eq!(actual, expected);
-            }
+        fn makewindows_short() {
+            //a 2 bp region cannot be split into 3 windows
+            let region = ChromSeg{chrom: "chr1".to_string(), start: 500, stop: 502};
+            let mut output = Vec::new();
+            assert!(!split_region(&mut output, &region, "tiny", &WindowConfig::new(Windows::Count(3))));
+            assert!(output.is_empty());
+            assert!(split_region(&mut output, &region, "tiny", &WindowConfig::new(Windows::Count(2))));
+            assert_eq!(String::from_utf8(output).unwrap(), "chr1\t500\t501\nchr1\t501\t502\n");
         }
     }
-}
\ No newline at end of file
+}
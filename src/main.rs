@@ -2,10 +2,222 @@ extern crate ironbed;
 #[macro_use]
 extern crate clap;
 
-use clap::{Arg, App, SubCommand};
+use std::io::{Write, BufWriter};
+use clap::{Arg, App, ArgGroup, SubCommand};
 use ironbed::union::union_main;
-use ironbed::random::{rand_bed, rand_bed_sorted};
+use ironbed::chrom_sizes::{ChromOrder, Genome, OutOfBounds, SizesMode, PRESETS};
+use ironbed::chrom_alias::{AliasTable, Renamer};
+use std::rc::Rc;
+use ironbed::closest::{closest_main, ClosestConfig, Orientation, Ties};
+use ironbed::complement::complement_main;
+use ironbed::coverage::{coverage_main, CoverageConfig, CoverageOutput};
+use ironbed::resize::{resize_main, Resize, ResizeConfig};
+use ironbed::subtract::{subtract_main, SubtractConfig};
+use ironbed::genomecov::{genomecov_main, CountEnd, CovConfig, CovOutput};
+use ironbed::intersect::{intersect_main, IntersectConfig, Report, Strandedness};
+use ironbed::makewindows::{makewindows_main, WindowConfig, WindowIds, WindowSource, Windows};
+use ironbed::map::{map_main, parse_columns as parse_map_columns, MapConfig};
+use ironbed::merge::{merge_main, parse_columns, MergeConfig};
+use ironbed::sort::{sort_main, parse_mem_size, SortConfig};
+use ironbed::random::{rand_bed, rand_bed_sorted, rand_bed_weighted, RandomConfig, Bed6Config, ValueDist};
 
+//load a genome for the '-g' argument, with the '--write-fai' and
+//'--tolerant' options, exiting on failure
+fn load_genome(fname: &str, matches: &clap::ArgMatches) -> Genome {
+    let mode = if matches.is_present("tolerant") { SizesMode::Tolerant } else { SizesMode::Strict };
+    Genome::load(fname, matches.is_present("write-fai"), mode).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+//load the alias table for '--aliases' and pick the naming convention
+//for '--rename-chroms' (without it, the aliases are only used to match
+//chromosomes, and the first name seen for each one is kept)
+fn load_renamer(matches: &clap::ArgMatches) -> Option<Rc<Renamer>> {
+    let fname = matches.value_of("aliases")?;
+    let renamer = AliasTable::from_file(fname).and_then(|table| match matches.value_of("rename-chroms") {
+        Some(convention) => Renamer::new(table, convention),
+        None => Ok(Renamer::keep_first(table)),
+    });
+    Some(Rc::new(renamer.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })))
+}
+
+//apply the '--aliases' and '--rename-chroms' options to a genome
+fn rename_genome(genome: Genome, renamer: Option<&Renamer>) -> Genome {
+    match renamer {
+        None => genome,
+        Some(renamer) => genome.with_aliases(renamer.table()).renamed(renamer).unwrap_or_else(|err| {
+            eprintln!("Error renaming genome: {}", err);
+            std::process::exit(1);
+        }),
+    }
+}
+
+//the '--bounds' option, which possible_values() has already checked
+fn parse_bounds(matches: &clap::ArgMatches) -> OutOfBounds {
+    matches.value_of("bounds").map_or(OutOfBounds::Reject, |mode| OutOfBounds::parse(mode).unwrap())
+}
+
+//the long options that bedtools spells with a single dash (e.g.
+//'intersect -wa'), for each subcommand
+const SINGLE_DASH_OPTIONS: &[(&str, &[&str])] = &[
+    ("intersect", &["-wa", "-wb", "-wo", "-sorted", "-names", "-filenames"]),
+    ("genomecov", &["-bg", "-bga", "-scale", "-strand"]),
+    ("slop", &["-pct"]),
+    ("flank", &["-pct"]),
+    ("closest", &["-io", "-iu", "-id"]),
+    ("coverage", &["-hist", "-mean"]),
+    ("map", &["-null"]),
+];
+
+//the command line, with the bedtools spellings of long options rewritten
+//to '--', which clap would otherwise read as a cluster of short flags
+fn bedtools_args() -> Vec<String> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let options = args.get(1).and_then(|cmd| SINGLE_DASH_OPTIONS.iter().find(|(name, _)| name == cmd)).map(|(_, options)| *options);
+    if let Some(options) = options {
+        for arg in args.iter_mut().skip(2) {
+            if options.contains(&arg.as_str()) {
+                arg.insert(0, '-');
+            }
+        }
+    }
+    args
+}
+
+//the help of a -g argument: what the genome file is for, then which
+//files it may be
+macro_rules! genome_help {
+    ($what:expr) => {
+        concat!($what, " <FILE> may be a chrom.sizes file, a FASTA index (.fai), a FASTA file, a SAM/BAM file, a VCF file, or the name of a built-in genome (see 'ironbed genome --list').")
+    };
+}
+
+//the help of a --bounds argument, for intervals or weights
+macro_rules! bounds_help {
+    ($what:expr) => {
+        concat!("What to do with ", $what, " on chromosomes missing from the genome file, or extending past their end: 'reject' (stop with an error), 'clip' or 'drop' them [default: reject]")
+    };
+}
+
+//the -g argument of the subcommands that read a genome file (see load_genome())
+fn genome_arg<'a, 'b>(help: &'b str) -> Arg<'a, 'b> {
+    Arg::with_name("genome")
+        .short("g")
+        .long("genome")
+        .takes_value(true)
+        .value_name("FILE")
+        .help(help)
+}
+
+fn tolerant_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("tolerant")
+        .long("tolerant")
+        .requires("genome")
+        .help("When the genome file is a chrom.sizes file, skip blank lines, '#' comments and a header line (e.g. 'chrom<TAB>size'), and ignore any columns after the first two")
+}
+
+fn write_fai_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("write-fai")
+        .long("write-fai")
+        .requires("genome")
+        .help("When the genome file is an uncompressed FASTA file, also write its index to '<FILE>.fai'")
+}
+
+//the --aliases and --rename-chroms arguments (see load_renamer())
+fn aliases_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("aliases")
+        .long("aliases")
+        .takes_value(true)
+        .value_name("FILE")
+        .help("Treat the chromosome names in alias table <FILE> (a UCSC chromAlias.txt file) as equivalent")
+}
+
+fn rename_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("rename-chroms")
+        .long("rename-chroms")
+        .takes_value(true)
+        .value_name("CONVENTION")
+        .requires("aliases")
+        .help("Name output chromosomes using the <CONVENTION> column of the alias table (e.g. 'ucsc', 'ensembl', 'refseq') [default: keep the names of the genome file, or of the first input that uses each chromosome]")
+}
+
+//see parse_bounds()
+fn bounds_arg<'a, 'b>(help: &'b str) -> Arg<'a, 'b> {
+    Arg::with_name("bounds")
+        .long("bounds")
+        .takes_value(true)
+        .value_name("MODE")
+        .possible_values(&["reject", "clip", "drop"])
+        .help(help)
+}
+
+//the arguments shared by slop, flank and shift
+fn resize_args<'a, 'b>(cmd: App<'a, 'b>) -> App<'a, 'b> {
+    cmd.version(crate_version!())
+       .arg(Arg::with_name("input")
+            .short("i")
+            .required(true)
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Input BED or bedGraph file. Columns after the third are kept."))
+       .arg(genome_arg(genome_help!("Genome file <FILE>, whose chromosome sizes the intervals are clipped to.")).required(true))
+       .arg(tolerant_arg())
+       .arg(Arg::with_name("pct")
+            .long("pct")
+            .help("The amounts are fractions of each interval's length (e.g. 0.1), instead of bp"))
+       .arg(bounds_arg(bounds_help!("intervals")))
+       .arg(aliases_arg())
+       .arg(rename_arg())
+}
+
+//the -b, -l, -r and -s arguments of slop and flank
+fn side_args<'a, 'b>(cmd: App<'a, 'b>) -> App<'a, 'b> {
+    cmd.group(ArgGroup::with_name("sides")
+              .args(&["both", "left", "right"])
+              .multiple(true)
+              .required(true))
+       .arg(Arg::with_name("both")
+            .short("b")
+            .takes_value(true)
+            .value_name("AMOUNT")
+            .help("The amount for both sides (-l and -r take precedence)"))
+       .arg(Arg::with_name("left")
+            .short("l")
+            .takes_value(true)
+            .value_name("AMOUNT")
+            .help("The amount for the start side (the stop, with -s on the '-' strand)"))
+       .arg(Arg::with_name("right")
+            .short("r")
+            .takes_value(true)
+            .value_name("AMOUNT")
+            .help("The amount for the stop side (the start, with -s on the '-' strand)"))
+       .arg(Arg::with_name("strand")
+            .short("s")
+            .help("Swap -l and -r for intervals on the '-' strand (column 6)"))
+}
+
+//parse an amount for slop, flank or shift, exiting on failure
+fn parse_amount(matches: &clap::ArgMatches, name: &str, flag: &str, signed: bool) -> f64 {
+    matches.value_of(name).map_or(0.0, |n| match n.parse::<f64>() {
+        Ok(amount) if signed || amount >= 0.0 => amount,
+        _ => {
+            eprintln!("Expected {} for {}, received '{}'", if signed { "number" } else { "non-negative number" }, flag, n);
+            std::process::exit(1);
+        }
+    })
+}
+
+//the sides of -b, -l and -r for slop and flank
+fn parse_sides(matches: &clap::ArgMatches) -> (f64, f64) {
+    let both = parse_amount(matches, "both", "-b", false);
+    let side = |name, flag| if matches.is_present(name) { parse_amount(matches, name, flag, false) } else { both };
+    (side("left", "-l"), side("right", "-r"))
+}
 
 fn main() {
     let matches = App::new("ironbed")
@@ -21,7 +233,7 @@ fn main() {
                                            .required(true)
                                            .takes_value(true)
                                            .value_name("FILE")
-                                           .help("Input bedGraph files. Input files cannot contain overlapping intervals and should be sorted by chrom, start. (Use the command 'sort -k1,1 -k2,2n for the correct order.')"))
+                                           .help("Input bedGraph files. Input files cannot contain overlapping intervals and should be sorted by chrom, start. (Use the command 'ironbed sort' or 'sort -k1,1 -k2,2n' for the correct order.)"))
                                       .arg(Arg::with_name("filler")
                                            .long("filler")
                                            .takes_value(true)
@@ -31,22 +243,27 @@ fn main() {
                                            .long("empty")
                                            .requires("genome")
                                            .help("Report empty regions (i.e. start/end intervals with no values in any file). Requires '-g <FILE>' parameter."))
-                                      .arg(Arg::with_name("genome")
-                                           .short("g")
-                                           .long("genome")
+                                      .arg(genome_arg(genome_help!("Use genome file <FILE> to calculate empty regions.")))
+                                      .arg(write_fai_arg())
+                                      .arg(tolerant_arg())
+                                      .arg(bounds_arg(bounds_help!("intervals")))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg())
+                                      .arg(Arg::with_name("presort")
+                                           .long("presort")
+                                           .help("Read the input files into memory and sort them before combining them, for small unsorted inputs"))
+                                      .arg(Arg::with_name("presort-mem")
+                                           .long("presort-mem")
                                            .takes_value(true)
-                                           .value_name("FILE")
-                                           .help("Use genome file <FILE> to calculate empty regions.")))
+                                           .value_name("SIZE")
+                                           .requires("presort")
+                                           .help("Stop with an error if the sorted inputs need more than <SIZE> of memory (e.g. '500M', '2G') [default: 1G]")))
                           .subcommand(SubCommand::with_name("random")
                                       .version(crate_version!())
                                       .about("Generate random BED files")
-                                      .arg(Arg::with_name("genome")
-                                           .short("g")
-                                           .long("genome")
-                                           .takes_value(true)
-                                           .required(true)
-                                           .value_name("FILE")
-                                           .help("Use genome file <FILE> for random values"))
+                                      .arg(genome_arg(genome_help!("Use genome file <FILE> for random values.")).required(true))
+                                      .arg(write_fai_arg())
+                                      .arg(tolerant_arg())
                                       .arg(Arg::with_name("lines")
                                            .short("n")
                                            .long("lines")
@@ -56,9 +273,482 @@ fn main() {
                                       .arg(Arg::with_name("sorted")
                                            .short("s")
                                            .long("--sorted")
+                                           .help("Output as sorted, non-intersecting BED, in the chromosome order of the genome file [without --lines, the genome is filled to capacity]"))
+                                      .arg(Arg::with_name("length")
+                                           .short("l")
+                                           .long("length")
+                                           .takes_value(true)
+                                           .value_name("NUM")
+                                           .help("Output segments of exactly <NUM> bases [default: random]"))
+                                      .arg(Arg::with_name("no-overlap")
+                                           .long("no-overlap")
                                            .requires("lines")
-                                           .help("Output as sorted, non-intersecting BED [requires --lines]")))
-                          .get_matches();
+                                           .help("Output exactly <NUM> non-overlapping segments, sorted by chrom, start [requires --lines]"))
+                                      .arg(Arg::with_name("bed6")
+                                           .long("bed6")
+                                           .help("Output BED6, with a name, score and strand for each segment"))
+                                      .arg(Arg::with_name("prefix")
+                                           .long("prefix")
+                                           .takes_value(true)
+                                           .value_name("TEXT")
+                                           .requires("bed6")
+                                           .help("Name each segment <TEXT> followed by its line number [default: '']"))
+                                      .arg(Arg::with_name("score")
+                                           .long("score")
+                                           .takes_value(true)
+                                           .value_name("NUM")
+                                           .requires("bed6")
+                                           .help("Use <NUM> as the score of every segment [default: segment length]"))
+                                      .arg(Arg::with_name("strand")
+                                           .long("strand")
+                                           .takes_value(true)
+                                           .value_name("STRAND")
+                                           .possible_values(&["+", "-"])
+                                           .requires("bed6")
+                                           .help("Put every segment on <STRAND> [default: random]"))
+                                      .arg(Arg::with_name("bedgraph")
+                                           .long("bedgraph")
+                                           .takes_value(true)
+                                           .value_name("DIST")
+                                           .conflicts_with("bed6")
+                                           .help("Output a sorted bedGraph with values from <DIST>: 'uniform:MIN,MAX', 'normal:MEAN,SD', 'exp:RATE' or 'poisson:MEAN'"))
+                                      .arg(Arg::with_name("gap-prob")
+                                           .long("gap-prob")
+                                           .takes_value(true)
+                                           .value_name("PROB")
+                                           .help("For sorted output, leave a gap between neighbouring segments with probability <PROB>; otherwise they are bookended [default: 1]"))
+                                      .arg(Arg::with_name("seed")
+                                           .long("seed")
+                                           .takes_value(true)
+                                           .value_name("NUM")
+                                           .help("Seed the random number generator with <NUM> for reproducible output"))
+                                      .arg(Arg::with_name("weights")
+                                           .short("w")
+                                           .long("weights")
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .conflicts_with_all(&["bedgraph", "no-overlap"])
+                                           .help("Sample segment starts in proportion to the values in bedGraph <FILE>. Segments are 1 base long unless --length is given. With --sorted, output follows the order of <FILE> [requires --lines]"))
+                                      .arg(bounds_arg(bounds_help!("weights")))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("sort")
+                                      .version(crate_version!())
+                                      .about("Sort BED and bedGraph files by chromosome, then start")
+                                      .arg(Arg::with_name("input")
+                                           .short("i")
+                                           .multiple(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Input BED or bedGraph files, which are sorted together ('-' for stdin) [default: stdin]"))
+                                      .arg(genome_arg(genome_help!("Sort the chromosomes in the order of genome file <FILE>.")).conflicts_with("natural"))
+                                      .arg(tolerant_arg())
+                                      .arg(bounds_arg(bounds_help!("intervals")).requires("genome"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg())
+                                      .arg(Arg::with_name("natural")
+                                           .long("natural")
+                                           .help("Sort the chromosomes in natural order (chr2 before chr10) instead of lexicographic order"))
+                                      .arg(Arg::with_name("max-mem")
+                                           .short("m")
+                                           .long("max-mem")
+                                           .takes_value(true)
+                                           .value_name("SIZE")
+                                           .help("Hold about <SIZE> of input in memory (e.g. '500M', '2G'), sorting larger inputs in temporary files [default: 1G]"))
+                                      .arg(Arg::with_name("tmpdir")
+                                           .short("T")
+                                           .long("tmpdir")
+                                           .takes_value(true)
+                                           .value_name("DIR")
+                                           .help("Write temporary files to <DIR> [default: the system temporary directory]")))
+                          .subcommand(SubCommand::with_name("merge")
+                                      .version(crate_version!())
+                                      .about("Merge overlapping intervals of a sorted BED or bedGraph file")
+                                      .arg(Arg::with_name("input")
+                                           .short("i")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Input BED or bedGraph file, sorted by chrom, then start (see 'ironbed sort')"))
+                                      .arg(Arg::with_name("distance")
+                                           .short("d")
+                                           .takes_value(true)
+                                           .allow_hyphen_values(true)
+                                           .value_name("INT")
+                                           .help("Merge intervals that are at most <INT> bp apart. Negative values require an overlap of at least that many bp [default: 0]"))
+                                      .arg(Arg::with_name("strand")
+                                           .short("s")
+                                           .help("Only merge intervals on the same strand (column 6)"))
+                                      .arg(Arg::with_name("columns")
+                                           .short("c")
+                                           .takes_value(true)
+                                           .value_name("COLS")
+                                           .help("Comma-separated columns to summarize for each merged interval"))
+                                      .arg(Arg::with_name("operations")
+                                           .short("o")
+                                           .takes_value(true)
+                                           .value_name("OPS")
+                                           .requires("columns")
+                                           .help("Comma-separated operations to apply to the columns given with -c: sum, mean, count, collapse, distinct, min, max [default: sum]"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("intersect")
+                                      .version(crate_version!())
+                                      .about("Report the overlaps between the intervals of two or more BED or bedGraph files")
+                                      .arg(Arg::with_name("a")
+                                           .short("a")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Each interval in <FILE> is compared with the intervals in the -b files"))
+                                      .arg(Arg::with_name("b")
+                                           .short("b")
+                                           .required(true)
+                                           .multiple(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("One or more files to compare with -a"))
+                                      .arg(Arg::with_name("wa")
+                                           .long("wa")
+                                           .help("Write the original A interval for each overlap, instead of the overlapping part"))
+                                      .arg(Arg::with_name("wb")
+                                           .long("wb")
+                                           .help("Also write the B interval (after its file label, if there are several -b files) for each overlap"))
+                                      .arg(Arg::with_name("wo")
+                                           .long("wo")
+                                           .help("Write the original A and B intervals and the number of overlapping bases for each overlap"))
+                                      .arg(Arg::with_name("unique")
+                                           .short("u")
+                                           .conflicts_with_all(&["invert", "count", "wa", "wb", "wo"])
+                                           .help("Write each A interval once if it overlaps any B interval"))
+                                      .arg(Arg::with_name("invert")
+                                           .short("v")
+                                           .conflicts_with_all(&["count", "wa", "wb", "wo"])
+                                           .help("Only write the A intervals that overlap no B interval"))
+                                      .arg(Arg::with_name("count")
+                                           .short("c")
+                                           .conflicts_with_all(&["wa", "wb", "wo"])
+                                           .help("Write each A interval with its number of overlaps"))
+                                      .arg(Arg::with_name("frac-a")
+                                           .short("f")
+                                           .takes_value(true)
+                                           .value_name("FRAC")
+                                           .help("Minimum overlap as a fraction of A [default: 1 bp]"))
+                                      .arg(Arg::with_name("frac-b")
+                                           .short("F")
+                                           .takes_value(true)
+                                           .value_name("FRAC")
+                                           .help("Minimum overlap as a fraction of B [default: 1 bp]"))
+                                      .arg(Arg::with_name("reciprocal")
+                                           .short("r")
+                                           .requires("frac-a")
+                                           .help("Require the -f fraction of B as well as of A"))
+                                      .arg(Arg::with_name("same-strand")
+                                           .short("s")
+                                           .help("Only report overlaps on the same strand (column 6)"))
+                                      .arg(Arg::with_name("opposite-strand")
+                                           .short("S")
+                                           .conflicts_with("same-strand")
+                                           .help("Only report overlaps on opposite strands (column 6)"))
+                                      .arg(Arg::with_name("sorted")
+                                           .long("sorted")
+                                           .help("Sweep through inputs sorted by chrom, then start, instead of reading the -b files into memory"))
+                                      .arg(genome_arg("With --sorted, the inputs are sorted in the chromosome order of genome file <FILE> instead of lexicographically").requires("sorted"))
+                                      .arg(bounds_arg(bounds_help!("intervals")).requires("genome"))
+                                      .arg(Arg::with_name("names")
+                                           .long("names")
+                                           .takes_value(true)
+                                           .multiple(true)
+                                           .value_name("NAME")
+                                           .conflicts_with("filenames")
+                                           .help("Labels for the -b files [default: 1, 2, ...]"))
+                                      .arg(Arg::with_name("filenames")
+                                           .long("filenames")
+                                           .help("Label the -b intervals with their file names"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("complement")
+                                      .version(crate_version!())
+                                      .about("Report the regions of a genome that are not covered by a BED or bedGraph file")
+                                      .arg(Arg::with_name("input")
+                                           .short("i")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Input BED or bedGraph file, sorted in the chromosome order of the genome file, then by start (see 'ironbed sort -g')"))
+                                      .arg(genome_arg(genome_help!("Genome file <FILE>, whose chromosomes are reported in order (in full, if they are not in the input).")).required(true))
+                                      .arg(tolerant_arg())
+                                      .arg(bounds_arg(bounds_help!("intervals")))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("genomecov")
+                                      .version(crate_version!())
+                                      .about("Compute the coverage of a genome by the intervals of a BED file")
+                                      .arg(Arg::with_name("input")
+                                           .short("i")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Input BED file (which need not be sorted)"))
+                                      .arg(genome_arg(genome_help!("Genome file <FILE>.")).required(true))
+                                      .arg(tolerant_arg())
+                                      .arg(Arg::with_name("bg")
+                                           .long("bg")
+                                           .help("Report the depth of the covered regions in bedGraph format, sorted for 'ironbed unionbedg'"))
+                                      .arg(Arg::with_name("bga")
+                                           .long("bga")
+                                           .conflicts_with("bg")
+                                           .help("Report the depth of every region in bedGraph format, including regions with no coverage"))
+                                      .arg(Arg::with_name("per-base")
+                                           .short("d")
+                                           .conflicts_with_all(&["bg", "bga"])
+                                           .help("Report the depth of every base (1-based)"))
+                                      .arg(Arg::with_name("scale")
+                                           .long("scale")
+                                           .takes_value(true)
+                                           .value_name("FACTOR")
+                                           .help("Multiply the depths by <FACTOR> in the --bg, --bga and -d output [default: 1]"))
+                                      .arg(Arg::with_name("strand")
+                                           .long("strand")
+                                           .takes_value(true)
+                                           .value_name("STRAND")
+                                           .possible_values(&["+", "-"])
+                                           .help("Only count intervals on strand <STRAND> (column 6)"))
+                                      .arg(Arg::with_name("five")
+                                           .short("5")
+                                           .help("Only count the 5' end of each interval"))
+                                      .arg(Arg::with_name("three")
+                                           .short("3")
+                                           .conflicts_with("five")
+                                           .help("Only count the 3' end of each interval"))
+                                      .arg(bounds_arg(bounds_help!("intervals")))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(side_args(resize_args(SubCommand::with_name("slop")
+                                      .about("Extend each interval of a BED or bedGraph file, within the chromosome"))))
+                          .subcommand(side_args(resize_args(SubCommand::with_name("flank")
+                                      .about("Report the regions on either side of each interval of a BED or bedGraph file, within the chromosome"))))
+                          .subcommand(resize_args(SubCommand::with_name("shift")
+                                      .about("Move each interval of a BED or bedGraph file, within the chromosome"))
+                                      .group(ArgGroup::with_name("amounts")
+                                             .args(&["shift", "plus", "minus"])
+                                             .multiple(true)
+                                             .required(true))
+                                      .arg(Arg::with_name("shift")
+                                           .short("s")
+                                           .takes_value(true)
+                                           .allow_hyphen_values(true)
+                                           .value_name("AMOUNT")
+                                           .help("Move every interval by <AMOUNT>, towards the start of the chromosome if it is negative (-p and -m take precedence)"))
+                                      .arg(Arg::with_name("plus")
+                                           .short("p")
+                                           .takes_value(true)
+                                           .allow_hyphen_values(true)
+                                           .value_name("AMOUNT")
+                                           .help("Move the intervals that are not on the '-' strand (column 6) by <AMOUNT>"))
+                                      .arg(Arg::with_name("minus")
+                                           .short("m")
+                                           .takes_value(true)
+                                           .allow_hyphen_values(true)
+                                           .value_name("AMOUNT")
+                                           .help("Move the intervals on the '-' strand (column 6) by <AMOUNT>")))
+                          .subcommand(SubCommand::with_name("closest")
+                                      .version(crate_version!())
+                                      .about("Report the closest interval in B to each interval in A")
+                                      .arg(Arg::with_name("a")
+                                           .short("a")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file A, sorted by chrom, then start (see 'ironbed sort')"))
+                                      .arg(Arg::with_name("b")
+                                           .short("b")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file B, sorted in the same order as A"))
+                                      .arg(Arg::with_name("distance")
+                                           .short("d")
+                                           .help("Write the distance to B after each pair (0 if they overlap, 1 if they are book-ended, -1 if there is no B interval)"))
+                                      .arg(Arg::with_name("signed")
+                                           .short("D")
+                                           .takes_value(true)
+                                           .value_name("MODE")
+                                           .possible_values(&["ref", "a", "b"])
+                                           .help("Like -d, but upstream distances are negative. Upstream is before A on the chromosome ('ref'), with respect to A's strand ('a'), or A upstream of B, with respect to B's strand ('b')"))
+                                      .arg(Arg::with_name("k")
+                                           .short("k")
+                                           .takes_value(true)
+                                           .value_name("N")
+                                           .help("Report the <N> closest intervals [default: 1]"))
+                                      .arg(Arg::with_name("io")
+                                           .long("io")
+                                           .help("Ignore B intervals that overlap A"))
+                                      .arg(Arg::with_name("iu")
+                                           .long("iu")
+                                           .requires("signed")
+                                           .help("Ignore B intervals upstream of A (following -D)"))
+                                      .arg(Arg::with_name("id")
+                                           .long("id")
+                                           .requires("signed")
+                                           .help("Ignore B intervals downstream of A (following -D)"))
+                                      .arg(Arg::with_name("ties")
+                                           .short("t")
+                                           .takes_value(true)
+                                           .value_name("MODE")
+                                           .possible_values(&["all", "first", "last"])
+                                           .help("Which of several equally close B intervals to report: 'all', or the 'first' or 'last' in B [default: all]"))
+                                      .arg(genome_arg("The inputs are sorted in the chromosome order of genome file <FILE>, instead of lexicographically"))
+                                      .arg(bounds_arg(bounds_help!("intervals")).requires("genome"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("subtract")
+                                      .version(crate_version!())
+                                      .about("Remove the parts of intervals in A that overlap intervals in B")
+                                      .arg(Arg::with_name("a")
+                                           .short("a")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file A; extra columns are kept"))
+                                      .arg(Arg::with_name("b")
+                                           .short("b")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file B (e.g. a blacklist), read into memory"))
+                                      .arg(Arg::with_name("whole")
+                                           .short("A")
+                                           .help("Remove the whole A interval if it overlaps B, instead of splitting it"))
+                                      .arg(Arg::with_name("frac")
+                                           .short("f")
+                                           .takes_value(true)
+                                           .value_name("FRAC")
+                                           .help("Only subtract B intervals that overlap this fraction of A [default: 1 bp]"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("coverage")
+                                      .version(crate_version!())
+                                      .about("Report the number of B intervals overlapping each interval in A, the bases covered, its length and the fraction covered")
+                                      .arg(Arg::with_name("a")
+                                           .short("a")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file A, sorted by chrom, then start (see 'ironbed sort')"))
+                                      .arg(Arg::with_name("b")
+                                           .short("b")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file B, sorted in the same order as A"))
+                                      .arg(Arg::with_name("hist")
+                                           .long("hist")
+                                           .conflicts_with_all(&["per-base", "mean"])
+                                           .help("Write a histogram of the depth over each interval in A, followed by one over all of A"))
+                                      .arg(Arg::with_name("per-base")
+                                           .short("d")
+                                           .conflicts_with("mean")
+                                           .help("Write the depth of every base of each interval in A (1-based, from the start of the interval)"))
+                                      .arg(Arg::with_name("mean")
+                                           .long("mean")
+                                           .help("Write the mean depth over each interval in A"))
+                                      .arg(genome_arg("The inputs are sorted in the chromosome order of genome file <FILE>, instead of lexicographically"))
+                                      .arg(bounds_arg(bounds_help!("intervals")).requires("genome"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("map")
+                                      .version(crate_version!())
+                                      .about("Summarize the values of the intervals in B (such as a bedGraph signal) over each interval in A")
+                                      .arg(Arg::with_name("a")
+                                           .short("a")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file A, sorted by chrom, then start (see 'ironbed sort')"))
+                                      .arg(Arg::with_name("b")
+                                           .short("b")
+                                           .required(true)
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("BED or bedGraph file B, sorted in the same order as A"))
+                                      .arg(Arg::with_name("columns")
+                                           .short("c")
+                                           .takes_value(true)
+                                           .value_name("COLS")
+                                           .help("Comma-separated columns of B to summarize [default: 5]"))
+                                      .arg(Arg::with_name("operations")
+                                           .short("o")
+                                           .takes_value(true)
+                                           .value_name("OPS")
+                                           .help("Comma-separated operations to apply to the columns given with -c: sum, mean, wmean (weighted by the bases overlapping A), median, min, max, count [default: sum]"))
+                                      .arg(Arg::with_name("null")
+                                           .long("null")
+                                           .takes_value(true)
+                                           .value_name("STRING")
+                                           .help("Write <STRING> for an interval in A with no values in B [default: .]"))
+                                      .arg(genome_arg("The inputs are sorted in the chromosome order of genome file <FILE>, instead of lexicographically"))
+                                      .arg(bounds_arg(bounds_help!("intervals")).requires("genome"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("makewindows")
+                                      .version(crate_version!())
+                                      .about("Split the chromosomes of a genome, or the intervals of a BED file, into windows")
+                                      .group(ArgGroup::with_name("source")
+                                             .args(&["genome", "bed"])
+                                             .required(true))
+                                      .group(ArgGroup::with_name("windows")
+                                             .args(&["size", "count"])
+                                             .required(true))
+                                      .arg(genome_arg(genome_help!("Split every chromosome of genome file <FILE>, in the order of the file.")))
+                                      .arg(tolerant_arg())
+                                      .arg(Arg::with_name("bed")
+                                           .short("b")
+                                           .takes_value(true)
+                                           .value_name("FILE")
+                                           .help("Split every interval of BED or bedGraph file <FILE>"))
+                                      .arg(Arg::with_name("size")
+                                           .short("w")
+                                           .takes_value(true)
+                                           .value_name("INT")
+                                           .help("Make windows of <INT> bp (the last window of each region may be shorter)"))
+                                      .arg(Arg::with_name("step")
+                                           .short("s")
+                                           .takes_value(true)
+                                           .value_name("INT")
+                                           .requires("size")
+                                           .help("Start a window every <INT> bp, for sliding windows [default: the window size]"))
+                                      .arg(Arg::with_name("count")
+                                           .short("n")
+                                           .takes_value(true)
+                                           .value_name("INT")
+                                           .help("Split each region into <INT> windows of nearly equal size (regions shorter than <INT> bp are skipped, with a warning)"))
+                                      .arg(Arg::with_name("ids")
+                                           .short("i")
+                                           .takes_value(true)
+                                           .value_name("MODE")
+                                           .possible_values(&["winnum", "srcwinnum"])
+                                           .help("Name each window with its number in the region ('winnum'), or with the region's name (column 4 of -b, or the chromosome), '_' and its number ('srcwinnum')"))
+                                      .arg(aliases_arg())
+                                      .arg(rename_arg()))
+                          .subcommand(SubCommand::with_name("genome")
+                                      .version(crate_version!())
+                                      .about("Print the chromosome sizes of a genome file or built-in genome")
+                                      .group(ArgGroup::with_name("source")
+                                             .args(&["genome", "list", "dump"])
+                                             .required(true))
+                                      .arg(genome_arg("Read chromosome sizes from <FILE>: a chrom.sizes file, a FASTA index (.fai), a FASTA file, a SAM/BAM file (@SQ lines), a VCF file (##contig lines) or the name of a built-in genome."))
+                                      .arg(write_fai_arg())
+                                      .arg(tolerant_arg())
+                                      .arg(Arg::with_name("list")
+                                           .long("list")
+                                           .help("List the built-in genomes"))
+                                      .arg(Arg::with_name("dump")
+                                           .long("dump")
+                                           .takes_value(true)
+                                           .value_name("NAME")
+                                           .help("Print the chromosome sizes of built-in genome <NAME>")))
+                          .get_matches_from(bedtools_args());
 
     match matches.subcommand() {
         ("unionbedg", Some(ubg_matches)) => {
@@ -66,31 +756,428 @@ fn main() {
             let filenames: Vec<&str> = ubg_matches.values_of("input").unwrap().collect();
             // filler has a default value of "0"
             let filler = ubg_matches.value_of("filler").unwrap_or("0");
-            union_main(filenames, filler, ubg_matches.is_present("empty"), ubg_matches.value_of("genome")).unwrap_or_else(|err| {
+            let renamer = load_renamer(ubg_matches);
+            let genome = ubg_matches.value_of("genome").map(|fname| load_genome(fname, ubg_matches))
+                                                     .map(|genome| rename_genome(genome, renamer.as_deref()));
+            let bounds = parse_bounds(ubg_matches);
+            let presort = if ubg_matches.is_present("presort") {
+                Some(ubg_matches.value_of("presort-mem").map_or(1 << 30, |size| parse_mem_size(size).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                })))
+            } else {
+                None
+            };
+            union_main(filenames, filler, ubg_matches.is_present("empty"), genome, renamer, bounds, presort).unwrap_or_else(|err| {
                 eprintln!("{}", err);
                 std::process::exit(1);
             });
         },
         ("random", Some(rand_matches)) => {
             //this operation is safe because --genome is required
-            let fname = rand_matches.value_of("genome").unwrap();
-            let n_lines = match rand_matches.value_of("lines") {
-                None => usize::max_value(),
-                Some(n) => n.parse().unwrap_or_else(| _ | {
-                    eprintln!("Expected unsigned integer for --lines, received '{}'", n);
+            let renamer = load_renamer(rand_matches);
+            let genome = load_genome(rand_matches.value_of("genome").unwrap(), rand_matches);
+            let genome = rename_genome(genome, renamer.as_deref());
+            let num_lines = rand_matches.value_of("lines").map(|n| n.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected unsigned integer for --lines, received '{}'", n);
+                std::process::exit(1);
+            }));
+            let length = rand_matches.value_of("length").map(|n| n.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected unsigned integer for --length, received '{}'", n);
+                std::process::exit(1);
+            }));
+            let bed6 = if rand_matches.is_present("bed6") {
+                let prefix = rand_matches.value_of("prefix").unwrap_or("").to_string();
+                let score = rand_matches.value_of("score").map(|n| n.parse().unwrap_or_else(| _ | {
+                    eprintln!("Expected unsigned integer for --score, received '{}'", n);
                     std::process::exit(1);
-                })
+                }));
+                //possible_values() guarantees a single character
+                let strand = rand_matches.value_of("strand").and_then(|s| s.chars().next());
+                Some(Bed6Config{prefix, score, strand})
+            } else {
+                None
             };
-            if rand_matches.is_present("sorted") {
-                rand_bed_sorted(fname, n_lines)
+            let bedgraph = rand_matches.value_of("bedgraph").map(|spec| ValueDist::parse(spec).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }));
+            let gap_prob = rand_matches.value_of("gap-prob").map_or(1.0, |p| p.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected number for --gap-prob, received '{}'", p);
+                std::process::exit(1);
+            }));
+            let seed = rand_matches.value_of("seed").map(|n| n.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected unsigned integer for --seed, received '{}'", n);
+                std::process::exit(1);
+            }));
+            let sorted = rand_matches.is_present("sorted") || rand_matches.is_present("no-overlap") || bedgraph.is_some();
+            let bounds = parse_bounds(rand_matches);
+            let config = RandomConfig{num_lines, length, bed6, bedgraph, gap_prob, seed, bounds, renamer};
+            if let Some(weights) = rand_matches.value_of("weights") {
+                rand_bed_weighted(&genome, weights, rand_matches.is_present("sorted"), &config)
+            } else if sorted {
+                rand_bed_sorted(&genome, &config)
             } else {
-                rand_bed(fname, n_lines)
+                rand_bed(&genome, &config)
             //check any errors
             }.unwrap_or_else(|err| {
                 eprintln!("{}", err);
                 std::process::exit(1);
             })
         }
+        ("sort", Some(sort_matches)) => {
+            let filenames: Vec<&str> = sort_matches.values_of("input").map_or(vec!["-"], |files| files.collect());
+            let renamer = load_renamer(sort_matches);
+            let order = if let Some(fname) = sort_matches.value_of("genome") {
+                ChromOrder::Genome(Rc::new(rename_genome(load_genome(fname, sort_matches), renamer.as_deref())))
+            } else if sort_matches.is_present("natural") {
+                ChromOrder::Natural
+            } else {
+                ChromOrder::Lexicographic
+            };
+            let mut config = SortConfig::new(order);
+            config.bounds = parse_bounds(sort_matches);
+            config.renamer = renamer;
+            if let Some(size) = sort_matches.value_of("max-mem") {
+                config.max_mem = parse_mem_size(size).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                });
+            }
+            if let Some(dir) = sort_matches.value_of("tmpdir") {
+                config.tmp_dir = dir.into();
+            }
+            sort_main(filenames, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("merge", Some(merge_matches)) => {
+            //this operation is safe because '-i' is required
+            let filename = merge_matches.value_of("input").unwrap();
+            let distance = merge_matches.value_of("distance").map_or(0, |n| n.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected integer for -d, received '{}'", n);
+                std::process::exit(1);
+            }));
+            let columns = match merge_matches.value_of("columns") {
+                Some(columns) => parse_columns(columns, merge_matches.value_of("operations").unwrap_or("sum")).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }),
+                None => Vec::new(),
+            };
+            let config = MergeConfig{distance, strand: merge_matches.is_present("strand"), columns, renamer: load_renamer(merge_matches)};
+            merge_main(filename, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("intersect", Some(int_matches)) => {
+            //these operations are safe because '-a' and '-b' are required
+            let a_file = int_matches.value_of("a").unwrap();
+            let b_files: Vec<&str> = int_matches.values_of("b").unwrap().collect();
+            let report = if int_matches.is_present("unique") {
+                Report::Unique
+            } else if int_matches.is_present("invert") {
+                Report::Missing
+            } else if int_matches.is_present("count") {
+                Report::Count
+            } else {
+                Report::Overlaps
+            };
+            let parse_frac = |arg: &str, flag: &str| int_matches.value_of(arg).map_or(0.0, |f| match f.parse::<f64>() {
+                Ok(frac) if frac > 0.0 && frac <= 1.0 => frac,
+                _ => {
+                    eprintln!("Expected a fraction between 0 and 1 for {}, received '{}'", flag, f);
+                    std::process::exit(1);
+                }
+            });
+            let strand = if int_matches.is_present("same-strand") {
+                Strandedness::Same
+            } else if int_matches.is_present("opposite-strand") {
+                Strandedness::Opposite
+            } else {
+                Strandedness::Any
+            };
+            let labels: Vec<String> = if let Some(names) = int_matches.values_of("names") {
+                let names: Vec<String> = names.map(|name| name.to_string()).collect();
+                if names.len() != b_files.len() {
+                    eprintln!("Expected {} names for the -b files, received {}", b_files.len(), names.len());
+                    std::process::exit(1);
+                }
+                names
+            } else if int_matches.is_present("filenames") {
+                b_files.iter().map(|fname| fname.to_string()).collect()
+            } else if b_files.len() > 1 {
+                (1..=b_files.len()).map(|n| n.to_string()).collect()
+            } else {
+                Vec::new()
+            };
+            let renamer = load_renamer(int_matches);
+            let sorted = if int_matches.is_present("sorted") {
+                Some(match int_matches.value_of("genome") {
+                    Some(fname) => ChromOrder::Genome(Rc::new(rename_genome(load_genome(fname, int_matches), renamer.as_deref()))),
+                    None => ChromOrder::Lexicographic,
+                })
+            } else {
+                None
+            };
+            let config = IntersectConfig{
+                report,
+                write_a: int_matches.is_present("wa"),
+                write_b: int_matches.is_present("wb"),
+                write_overlap: int_matches.is_present("wo"),
+                min_frac_a: parse_frac("frac-a", "-f"),
+                min_frac_b: parse_frac("frac-b", "-F"),
+                reciprocal: int_matches.is_present("reciprocal"),
+                strand,
+                labels,
+                sorted,
+                bounds: parse_bounds(int_matches),
+                renamer,
+            };
+            intersect_main(a_file, &b_files, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("complement", Some(comp_matches)) => {
+            //these operations are safe because '-i' and '-g' are required
+            let filename = comp_matches.value_of("input").unwrap();
+            let renamer = load_renamer(comp_matches);
+            let genome = rename_genome(load_genome(comp_matches.value_of("genome").unwrap(), comp_matches), renamer.as_deref());
+            complement_main(filename, genome, parse_bounds(comp_matches), renamer).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("genomecov", Some(cov_matches)) => {
+            //these operations are safe because '-i' and '-g' are required
+            let filename = cov_matches.value_of("input").unwrap();
+            let renamer = load_renamer(cov_matches);
+            let genome = rename_genome(load_genome(cov_matches.value_of("genome").unwrap(), cov_matches), renamer.as_deref());
+            let output = if cov_matches.is_present("bg") {
+                CovOutput::BedGraph
+            } else if cov_matches.is_present("bga") {
+                CovOutput::BedGraphAll
+            } else if cov_matches.is_present("per-base") {
+                CovOutput::PerBase
+            } else {
+                CovOutput::Histogram
+            };
+            let scale = cov_matches.value_of("scale").map_or(1.0, |f| f.parse().unwrap_or_else(| _ | {
+                eprintln!("Expected number for --scale, received '{}'", f);
+                std::process::exit(1);
+            }));
+            let end = if cov_matches.is_present("five") {
+                CountEnd::Five
+            } else if cov_matches.is_present("three") {
+                CountEnd::Three
+            } else {
+                CountEnd::Whole
+            };
+            //possible_values() guarantees a single character
+            let strand = cov_matches.value_of("strand").and_then(|s| s.chars().next());
+            let config = CovConfig{output, scale, strand, end, bounds: parse_bounds(cov_matches), renamer};
+            genomecov_main(filename, genome, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        (name @ "slop", Some(resize_matches)) | (name @ "flank", Some(resize_matches)) | (name @ "shift", Some(resize_matches)) => {
+            //these operations are safe because '-i' and '-g' are required
+            let filename = resize_matches.value_of("input").unwrap();
+            let renamer = load_renamer(resize_matches);
+            let genome = rename_genome(load_genome(resize_matches.value_of("genome").unwrap(), resize_matches), renamer.as_deref());
+            let resize = match name {
+                "slop" => {
+                    let (left, right) = parse_sides(resize_matches);
+                    Resize::Slop{left, right}
+                },
+                "flank" => {
+                    let (left, right) = parse_sides(resize_matches);
+                    Resize::Flank{left, right}
+                },
+                _ => {
+                    let shift = parse_amount(resize_matches, "shift", "-s", true);
+                    let strand = |name, flag| if resize_matches.is_present(name) { parse_amount(resize_matches, name, flag, true) } else { shift };
+                    Resize::Shift{plus: strand("plus", "-p"), minus: strand("minus", "-m")}
+                },
+            };
+            let config = ResizeConfig{
+                resize,
+                pct: resize_matches.is_present("pct"),
+                strand: name != "shift" && resize_matches.is_present("strand"),
+                bounds: parse_bounds(resize_matches),
+                renamer,
+            };
+            resize_main(filename, genome, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("closest", Some(cl_matches)) => {
+            //these operations are safe because '-a' and '-b' are required
+            let a_file = cl_matches.value_of("a").unwrap();
+            let b_file = cl_matches.value_of("b").unwrap();
+            let k = cl_matches.value_of("k").map_or(1, |n| match n.parse() {
+                Ok(k) if k > 0 => k,
+                _ => {
+                    eprintln!("Expected positive integer for -k, received '{}'", n);
+                    std::process::exit(1);
+                }
+            });
+            //possible_values() has already checked these
+            let signed = cl_matches.value_of("signed").map(|mode| Orientation::parse(mode).unwrap());
+            let ties = match cl_matches.value_of("ties") {
+                Some("first") => Ties::First,
+                Some("last") => Ties::Last,
+                _ => Ties::All,
+            };
+            let renamer = load_renamer(cl_matches);
+            let order = match cl_matches.value_of("genome") {
+                Some(fname) => ChromOrder::Genome(Rc::new(rename_genome(load_genome(fname, cl_matches), renamer.as_deref()))),
+                None => ChromOrder::Lexicographic,
+            };
+            let config = ClosestConfig{
+                report_distance: cl_matches.is_present("distance"),
+                signed,
+                k,
+                ignore_overlaps: cl_matches.is_present("io"),
+                ignore_upstream: cl_matches.is_present("iu"),
+                ignore_downstream: cl_matches.is_present("id"),
+                ties,
+                order,
+                bounds: parse_bounds(cl_matches),
+                renamer,
+            };
+            closest_main(a_file, b_file, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("subtract", Some(sub_matches)) => {
+            //these operations are safe because '-a' and '-b' are required
+            let a_file = sub_matches.value_of("a").unwrap();
+            let b_file = sub_matches.value_of("b").unwrap();
+            let min_frac = sub_matches.value_of("frac").map_or(0.0, |f| match f.parse::<f64>() {
+                Ok(frac) if frac > 0.0 && frac <= 1.0 => frac,
+                _ => {
+                    eprintln!("Expected a fraction between 0 and 1 for -f, received '{}'", f);
+                    std::process::exit(1);
+                }
+            });
+            let config = SubtractConfig{whole: sub_matches.is_present("whole"), min_frac, renamer: load_renamer(sub_matches)};
+            subtract_main(a_file, b_file, config).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("coverage", Some(cov_matches)) => {
+            //these operations are safe because '-a' and '-b' are required
+            let a_file = cov_matches.value_of("a").unwrap();
+            let b_file = cov_matches.value_of("b").unwrap();
+            let output = if cov_matches.is_present("hist") {
+                CoverageOutput::Histogram
+            } else if cov_matches.is_present("per-base") {
+                CoverageOutput::PerBase
+            } else if cov_matches.is_present("mean") {
+                CoverageOutput::Mean
+            } else {
+                CoverageOutput::Counts
+            };
+            let renamer = load_renamer(cov_matches);
+            let order = match cov_matches.value_of("genome") {
+                Some(fname) => ChromOrder::Genome(Rc::new(rename_genome(load_genome(fname, cov_matches), renamer.as_deref()))),
+                None => ChromOrder::Lexicographic,
+            };
+            coverage_main(a_file, b_file, CoverageConfig{output, order, bounds: parse_bounds(cov_matches), renamer}).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("map", Some(map_matches)) => {
+            //these operations are safe because '-a' and '-b' are required
+            let a_file = map_matches.value_of("a").unwrap();
+            let b_file = map_matches.value_of("b").unwrap();
+            let columns = parse_map_columns(map_matches.value_of("columns").unwrap_or("5"),
+                                            map_matches.value_of("operations").unwrap_or("sum")).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+            let renamer = load_renamer(map_matches);
+            let order = match map_matches.value_of("genome") {
+                Some(fname) => ChromOrder::Genome(Rc::new(rename_genome(load_genome(fname, map_matches), renamer.as_deref()))),
+                None => ChromOrder::Lexicographic,
+            };
+            let null = map_matches.value_of("null").unwrap_or(".").to_string();
+            map_main(a_file, b_file, MapConfig{columns, null, order, bounds: parse_bounds(map_matches), renamer}).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("makewindows", Some(win_matches)) => {
+            let parse_positive = |arg: &str, flag: &str| win_matches.value_of(arg).map(|n| match n.parse() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    eprintln!("Expected positive integer for {}, received '{}'", flag, n);
+                    std::process::exit(1);
+                }
+            });
+            //one of '-w' and '-n' is required
+            let windows = match parse_positive("size", "-w") {
+                Some(size) => Windows::Size{size, step: parse_positive("step", "-s").unwrap_or(size)},
+                None => Windows::Count(parse_positive("count", "-n").unwrap()),
+            };
+            let ids = match win_matches.value_of("ids") {
+                Some("winnum") => WindowIds::WinNum,
+                Some("srcwinnum") => WindowIds::SrcWinNum,
+                _ => WindowIds::None,
+            };
+            let renamer = load_renamer(win_matches);
+            let config = WindowConfig{windows, ids, renamer: renamer.clone()};
+            //one of '-g' and '-b' is required
+            let result = match win_matches.value_of("genome") {
+                Some(fname) => makewindows_main(WindowSource::Genome(&rename_genome(load_genome(fname, win_matches), renamer.as_deref())), config),
+                None => makewindows_main(WindowSource::Bed(win_matches.value_of("bed").unwrap()), config),
+            };
+            result.unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            });
+        },
+        ("genome", Some(genome_matches)) => {
+            let mut output = BufWriter::new(std::io::stdout());
+            let result = if genome_matches.is_present("list") {
+                PRESETS.iter().try_for_each(|(name, _)| {
+                    //the presets are tested, so they always parse
+                    let genome = Genome::preset(name).unwrap().unwrap();
+                    writeln!(output, "{}\t{} chromosomes\t{} bp", name, genome.len(), genome.total_len())
+                })
+            } else if let Some(name) = genome_matches.value_of("dump") {
+                match Genome::preset(name) {
+                    Some(genome) => genome.unwrap().write_sizes(&mut output),
+                    None => {
+                        let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+                        eprintln!("Unknown genome '{}' (expected one of: {})", name, names.join(", "));
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                //the "source" group guarantees that one of the options is present
+                let genome = load_genome(genome_matches.value_of("genome").unwrap(), genome_matches);
+                genome.write_sizes(&mut output)
+            };
+            result.and_then(|_| output.flush()).unwrap_or_else(|err| {
+                match err.kind() {
+                    std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+                    _ => {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
+                    }
+                }
+            });
+        },
         ("", None) => eprintln!("No subcommand provided. Try 'ironbed help' for available subcommands."),
         _ => unreachable!(),
     }